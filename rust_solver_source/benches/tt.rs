@@ -0,0 +1,65 @@
+//! Effect of the heuristic transposition table on the bundled maps.
+//!
+//! Run with `cargo bench --bench tt`. Each map is solved with the cache
+//! disabled and at its default size; maps that take longer than
+//! `TIME_LIMIT` without the cache are skipped.
+
+use rust_solver::{Puzzle, SolveError, SolveOptions, Solver, DEFAULT_TT_SIZE};
+use std::fs;
+use std::time::{Duration, Instant};
+
+const TIME_LIMIT: Duration = Duration::from_secs(5);
+
+const MAPS: [&str; 10] = [
+    "threeboxes2",
+    "fourboxes2",
+    "fourboxes3",
+    "fiveboxes2",
+    "fiveboxes3",
+    "level3",
+    "original1",
+    "8boxes1",
+    "8Boxes2",
+    "8boxes3",
+];
+
+fn main() {
+    println!("{:<12} {:>10} {:>10} {:>10} {:>10} {:>8}", "map", "off (ms)", "on (ms)", "hits", "misses", "hit %");
+
+    for name in MAPS {
+        let path = format!("{}/../maps/{}.txt", env!("CARGO_MANIFEST_DIR"), name);
+        let puzzle = match fs::read_to_string(&path).map(|text| Puzzle::parse(&text)) {
+            Ok(Ok(puzzle)) => puzzle,
+            _ => continue,
+        };
+
+        let mut timings = [0.0; 2];
+        let mut stats = Default::default();
+        let mut timed_out = false;
+        for (i, tt_size) in [0, DEFAULT_TT_SIZE].into_iter().enumerate() {
+            let options = SolveOptions { tt_size, time_limit: Some(TIME_LIMIT), ..Default::default() };
+            let mut solver = Solver::new(&puzzle);
+            let start = Instant::now();
+            timed_out |= matches!(solver.solve(&options), Err(SolveError::Timeout(_)));
+            timings[i] = start.elapsed().as_secs_f64() * 1000.0;
+            stats = solver.stats().clone();
+            if timed_out {
+                break;
+            }
+        }
+        if timed_out {
+            continue;
+        }
+
+        let lookups = (stats.tt_hits + stats.tt_misses).max(1);
+        println!(
+            "{:<12} {:>10.1} {:>10.1} {:>10} {:>10} {:>7.1}%",
+            name,
+            timings[0],
+            timings[1],
+            stats.tt_hits,
+            stats.tt_misses,
+            100.0 * stats.tt_hits as f64 / lookups as f64
+        );
+    }
+}
@@ -0,0 +1,228 @@
+//! `rust_solver batch`: solve every level of a directory or collection.
+
+use rayon::prelude::*;
+use rayon::ThreadPoolBuilder;
+use rust_solver::{Collection, Puzzle, SolveError};
+use serde_json::{json, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::{
+    exit_code, load_deadlock_db, options_json, parse_args, read_input, report_json, solve_puzzle, Report, EXIT_IO,
+    EXIT_USAGE, STDIN_PATH, USAGE,
+};
+
+const DEFAULT_LEVEL_TIME_LIMIT: Duration = Duration::from_secs(10);
+const LEVEL_EXTENSIONS: [&str; 3] = ["txt", "sok", "xsb"];
+
+struct Entry {
+    name: String,
+    title: Option<String>,
+    puzzle: Result<Puzzle, SolveError>,
+}
+
+/// Levels of one file. A file holding a single level is named after the
+/// file; levels of a collection get their 1-based number appended.
+fn file_entries(stem: &str, text: &str, entries: &mut Vec<Entry>) {
+    let levels = Collection::parse(text).levels;
+    let single = levels.len() == 1;
+    for (index, level) in levels.into_iter().enumerate() {
+        let name = if single { stem.to_string() } else { format!("{}#{}", stem, index + 1) };
+        let puzzle = level.puzzle();
+        entries.push(Entry { name, title: level.title, puzzle });
+    }
+}
+
+fn collect_entries(path: &str) -> Result<Vec<Entry>, SolveError> {
+    let mut entries = Vec::new();
+    if path == STDIN_PATH {
+        file_entries("stdin", &read_input(path)?, &mut entries);
+        return Ok(entries);
+    }
+    let path = Path::new(path);
+    let stem = |file: &Path| {
+        file.file_stem().map_or_else(|| file.display().to_string(), |s| s.to_string_lossy().into_owned())
+    };
+    let path_str = path.display().to_string();
+    if !path.is_dir() {
+        file_entries(&stem(path), &read_input(&path_str)?, &mut entries);
+        return Ok(entries);
+    }
+
+    let io_error = |e: std::io::Error| SolveError::Io { path: path_str.clone(), message: e.to_string() };
+    let mut files: Vec<PathBuf> = fs::read_dir(path)
+        .map_err(io_error)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|file| {
+            file.extension()
+                .is_some_and(|ext| LEVEL_EXTENSIONS.iter().any(|known| ext.eq_ignore_ascii_case(known)))
+        })
+        .collect();
+    files.sort();
+    for file in files {
+        file_entries(&stem(&file), &read_input(&file.display().to_string())?, &mut entries);
+    }
+    Ok(entries)
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn write_csv(path: &str, names: &[String], titles: &[Option<String>], reports: &[Report]) -> std::io::Result<()> {
+    let mut csv = String::from("level,title,status,moves,pushes,nodes_expanded,nodes_generated,elapsed_ms\n");
+    for ((name, title), report) in names.iter().zip(titles).zip(reports) {
+        let solution = report.result.as_ref().ok();
+        let optional = |value: Option<String>| value.unwrap_or_default();
+        csv.push_str(&format!(
+            "{},{},{},{},{},{},{},{:.3}\n",
+            csv_field(name),
+            csv_field(title.as_deref().unwrap_or_default()),
+            status(report),
+            optional(solution.map(|s| s.moves.len().to_string())),
+            optional(solution.map(|s| s.pushes.to_string())),
+            optional(report.stats.as_ref().map(|s| s.nodes_expanded.to_string())),
+            optional(report.stats.as_ref().map(|s| s.nodes_generated.to_string())),
+            report.elapsed.as_secs_f64() * 1000.0
+        ));
+    }
+    fs::write(path, csv)
+}
+
+fn status(report: &Report) -> &'static str {
+    match &report.result {
+        Ok(_) => "solved",
+        Err(err) => err.name(),
+    }
+}
+
+fn print_table(names: &[String], reports: &[Report], total: Duration) {
+    let width = names.iter().map(String::len).max().unwrap_or(0).max("level".len());
+    println!("{:<width$}  {:<14} {:>7} {:>7} {:>10} {:>10}", "level", "status", "moves", "pushes", "nodes", "time(ms)");
+    for (name, report) in names.iter().zip(reports) {
+        let solution = report.result.as_ref().ok();
+        let cell = |value: Option<String>| value.unwrap_or_else(|| "-".to_string());
+        println!(
+            "{:<width$}  {:<14} {:>7} {:>7} {:>10} {:>10.1}",
+            name,
+            status(report),
+            cell(solution.map(|s| s.moves.len().to_string())),
+            cell(solution.map(|s| s.pushes.to_string())),
+            cell(report.stats.as_ref().map(|s| s.nodes_expanded.to_string())),
+            report.elapsed.as_secs_f64() * 1000.0
+        );
+    }
+    let solved = reports.iter().filter(|r| r.result.is_ok()).count();
+    println!("solved {}/{} in {:.1} s", solved, reports.len(), total.as_secs_f64());
+}
+
+/// `batch [options] [--jobs <n>] [--report <file>] <directory|collection>`;
+/// returns the exit code. Unsolved levels do not change the exit code.
+pub(crate) fn run(args: &[String]) -> i32 {
+    let mut jobs = 1;
+    let mut report_path = None;
+    let mut rest = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--jobs" => match iter.next().and_then(|v| v.parse().ok()).filter(|&n: &usize| n > 0) {
+                Some(n) => jobs = n,
+                None => return usage("--jobs needs a positive number"),
+            },
+            "--report" => match iter.next() {
+                Some(path) => report_path = Some(path.clone()),
+                None => return usage("--report needs a value"),
+            },
+            // Every level is solved and reported the same way.
+            "--level" | "--lowercase" | "--format" => return usage(&format!("batch does not take {}", arg)),
+            _ => rest.push(arg.clone()),
+        }
+    }
+    let mut args = match parse_args(&rest) {
+        Ok(args) => args,
+        Err(msg) => return usage(&msg),
+    };
+    args.options.time_limit.get_or_insert(DEFAULT_LEVEL_TIME_LIMIT);
+
+    let db = match load_deadlock_db(&args) {
+        Ok(db) => db,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            return exit_code(&err);
+        }
+    };
+    let entries = match collect_entries(&args.puzzle_path) {
+        Ok(entries) => entries,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            return EXIT_IO;
+        }
+    };
+    let pool = match ThreadPoolBuilder::new().num_threads(jobs).build() {
+        Ok(pool) => pool,
+        Err(err) => {
+            eprintln!("error: cannot start {} jobs: {}", jobs, err);
+            return EXIT_USAGE;
+        }
+    };
+
+    let started = Instant::now();
+    let mut names = Vec::with_capacity(entries.len());
+    let mut titles = Vec::with_capacity(entries.len());
+    let mut puzzles = Vec::with_capacity(entries.len());
+    for entry in entries {
+        names.push(entry.name);
+        titles.push(entry.title);
+        puzzles.push(entry.puzzle);
+    }
+    let solve = |puzzle| solve_puzzle(puzzle, &args.options, db.as_ref());
+    let reports: Vec<Report> = pool.install(|| puzzles.into_par_iter().map(solve).collect());
+    print_table(&names, &reports, started.elapsed());
+
+    if let (Some(path), Some(db)) = (&args.deadlock_db, db) {
+        if let Err(err) = db.into_inner().save(path) {
+            eprintln!("error: {}", err);
+            return EXIT_IO;
+        }
+    }
+
+    if let Some(path) = report_path {
+        let written = if path.ends_with(".json") {
+            let levels: Vec<Value> = names
+                .iter()
+                .zip(&titles)
+                .zip(&reports)
+                .map(|((name, title), report)| {
+                    let mut level = report_json(name, report);
+                    level["title"] = json!(title);
+                    level
+                })
+                .collect();
+            let json = json!({
+                "source": args.puzzle_path,
+                "solved": reports.iter().filter(|r| r.result.is_ok()).count(),
+                "total": reports.len(),
+                "config": options_json(&args.options),
+                "levels": levels,
+            });
+            fs::write(&path, format!("{:#}\n", json))
+        } else {
+            write_csv(&path, &names, &titles, &reports)
+        };
+        if let Err(err) = written {
+            eprintln!("error: cannot write {}: {}", path, err);
+            return EXIT_IO;
+        }
+    }
+    0
+}
+
+fn usage(msg: &str) -> i32 {
+    eprintln!("error: {}\n\n{}", msg, USAGE);
+    EXIT_USAGE
+}
@@ -0,0 +1,134 @@
+use rustc_hash::{FxHashMap, FxHashSet};
+use smallvec::SmallVec;
+
+/// Exact identity of a search state: a bitset of box squares over the
+/// map's non-wall cells, followed by one word holding the player key square.
+pub(crate) type StateKey = SmallVec<[u64; 4]>;
+
+/// How the closed set identifies states it has already expanded.
+///
+/// `HashOnly` stores just the 64-bit Zobrist hash: smallest and fastest, but
+/// two different states with the same hash are treated as one, so a
+/// collision can silently prune a state and even hide every solution.
+/// `Exact` stores the full state key as well and never prunes wrongly; it
+/// also counts how many hash collisions it had to resolve.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ClosedSetMode {
+    #[default]
+    Exact,
+    HashOnly,
+}
+
+impl ClosedSetMode {
+    pub const NAMES: [&'static str; 2] = ["exact", "hash"];
+
+    pub fn from_name(name: &str) -> Option<ClosedSetMode> {
+        match name {
+            "exact" => Some(ClosedSetMode::Exact),
+            "hash" => Some(ClosedSetMode::HashOnly),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ClosedSetMode::Exact => "exact",
+            ClosedSetMode::HashOnly => "hash",
+        }
+    }
+}
+
+pub(crate) enum ClosedSet {
+    HashOnly(FxHashSet<u64>),
+    Exact {
+        // First state seen under each hash; states whose hash is already
+        // taken by a different state go to `overflow`.
+        primary: FxHashMap<u64, StateKey>,
+        overflow: FxHashSet<StateKey>,
+        collisions: u64,
+    },
+}
+
+impl ClosedSet {
+    pub(crate) fn new(mode: ClosedSetMode, capacity: usize) -> Self {
+        match mode {
+            ClosedSetMode::HashOnly => {
+                ClosedSet::HashOnly(FxHashSet::with_capacity_and_hasher(capacity, Default::default()))
+            }
+            ClosedSetMode::Exact => ClosedSet::Exact {
+                primary: FxHashMap::with_capacity_and_hasher(capacity, Default::default()),
+                overflow: FxHashSet::default(),
+                collisions: 0,
+            },
+        }
+    }
+
+    /// Adds a state; returns false if it was already present. `key` is only
+    /// called in exact mode.
+    #[inline]
+    pub(crate) fn insert(&mut self, hash: u64, key: impl FnOnce() -> StateKey) -> bool {
+        match self {
+            ClosedSet::HashOnly(set) => set.insert(hash),
+            ClosedSet::Exact { primary, overflow, collisions } => {
+                let key = key();
+                match primary.get(&hash) {
+                    None => {
+                        primary.insert(hash, key);
+                        true
+                    }
+                    Some(existing) if *existing == key => false,
+                    Some(_) => {
+                        let inserted = overflow.insert(key);
+                        if inserted {
+                            *collisions += 1;
+                        }
+                        inserted
+                    }
+                }
+            }
+        }
+    }
+
+    #[inline]
+    pub(crate) fn contains(&self, hash: u64, key: impl FnOnce() -> StateKey) -> bool {
+        match self {
+            ClosedSet::HashOnly(set) => set.contains(&hash),
+            ClosedSet::Exact { primary, overflow, .. } => match primary.get(&hash) {
+                None => false,
+                Some(existing) => {
+                    let key = key();
+                    *existing == key || overflow.contains(&key)
+                }
+            },
+        }
+    }
+
+    /// States stored so far.
+    pub(crate) fn len(&self) -> usize {
+        match self {
+            ClosedSet::HashOnly(set) => set.len(),
+            ClosedSet::Exact { primary, overflow, .. } => primary.len() + overflow.len(),
+        }
+    }
+
+    /// Rough heap footprint, for enforcing memory limits.
+    pub(crate) fn approx_bytes(&self) -> usize {
+        use std::mem::size_of;
+        match self {
+            ClosedSet::HashOnly(set) => set.capacity() * (size_of::<u64>() + 1),
+            ClosedSet::Exact { primary, overflow, .. } => {
+                primary.capacity() * (size_of::<(u64, StateKey)>() + 1)
+                    + overflow.capacity() * (size_of::<StateKey>() + 1)
+            }
+        }
+    }
+
+    /// Hash collisions between distinct states seen so far (always 0 in
+    /// hash-only mode, which cannot tell).
+    pub(crate) fn collisions(&self) -> u64 {
+        match self {
+            ClosedSet::HashOnly(_) => 0,
+            ClosedSet::Exact { collisions, .. } => *collisions,
+        }
+    }
+}
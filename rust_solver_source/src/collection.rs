@@ -0,0 +1,174 @@
+use std::fs;
+use std::path::Path;
+
+use crate::error::SolveError;
+use crate::puzzle::Puzzle;
+
+/// One level of a collection file.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Level {
+    /// The level's map rows, ready for [`Puzzle::parse`].
+    pub text: String,
+    /// 1-based line of the file where the map starts.
+    pub line: usize,
+    pub title: Option<String>,
+    pub author: Option<String>,
+    /// `;` comments and any other metadata lines, in file order.
+    pub comments: Vec<String>,
+}
+
+impl Level {
+    pub fn puzzle(&self) -> Result<Puzzle, SolveError> {
+        Puzzle::parse(&self.text)
+    }
+}
+
+/// A `.sok`/`.xsb`-style file holding any number of levels.
+///
+/// Maps are runs of map rows. Metadata is read from the other lines: in a
+/// paragraph (lines between blank lines) that holds a map, every other line
+/// describes that map (or, between two maps, the earlier one); a paragraph
+/// without a map describes the next map. The paragraphs before the first
+/// level, except the one right above it, describe the collection itself.
+/// `Title:` and `Author:` set those fields, a bare line sets the title if
+/// there is none yet, and everything else (`;` comments, `Comment:` blocks,
+/// other keys) is kept as a comment.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Collection {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub comments: Vec<String>,
+    pub levels: Vec<Level>,
+}
+
+/// Whether a line belongs to a map: non-blank, made only of map characters
+/// (RLE counts, `|` and the `-`/`_` floor included), and containing at least
+/// one wall (so prose like "...." is not a row).
+fn is_map_row(line: &str) -> bool {
+    let line = line.trim_end();
+    !line.is_empty()
+        && line.contains('#')
+        && line
+            .chars()
+            .all(|c| c.is_ascii_digit() || matches!(c, '#' | '@' | '+' | '$' | '*' | '.' | ' ' | '-' | '_' | '|'))
+}
+
+/// Files one metadata line under title, author or comments.
+fn add_metadata(line: &str, title: &mut Option<String>, author: &mut Option<String>, comments: &mut Vec<String>) {
+    let line = line.trim();
+    if let Some(comment) = line.strip_prefix(';') {
+        comments.push(comment.trim().to_string());
+        return;
+    }
+    let (key, value) = match line.split_once(':') {
+        Some((key, value)) if !key.contains(' ') => (key.to_ascii_lowercase(), value.trim()),
+        _ => (String::new(), line),
+    };
+    match key.as_str() {
+        "title" => *title = Some(value.to_string()),
+        "author" => *author = Some(value.to_string()),
+        "comment" | "comment-end" if value.is_empty() => {}
+        "comment" => comments.push(value.to_string()),
+        "" if title.is_none() => *title = Some(line.to_string()),
+        _ => comments.push(line.to_string()),
+    }
+}
+
+impl Level {
+    fn add_metadata(&mut self, line: &str) {
+        add_metadata(line, &mut self.title, &mut self.author, &mut self.comments);
+    }
+}
+
+impl Collection {
+    pub fn parse(text: &str) -> Collection {
+        let lines: Vec<&str> = text.lines().collect();
+        let mut collection = Collection::default();
+        // Map-less paragraphs seen since the last level.
+        let mut pending: Vec<&[&str]> = Vec::new();
+
+        let mut start = 0;
+        while start < lines.len() {
+            if lines[start].trim().is_empty() {
+                start += 1;
+                continue;
+            }
+            let end = (start..lines.len()).find(|&i| lines[i].trim().is_empty()).unwrap_or(lines.len());
+            let paragraph = &lines[start..end];
+            let first_line = start;
+            start = end;
+
+            if !paragraph.iter().any(|line| is_map_row(line)) {
+                pending.push(paragraph);
+                continue;
+            }
+            if collection.levels.is_empty() && pending.len() > 1 {
+                for line in pending.drain(..pending.len() - 1).flatten() {
+                    collection.add_metadata(line);
+                }
+            }
+
+            let mut level = Level::default();
+            for line in pending.drain(..).flatten() {
+                level.add_metadata(line);
+            }
+            let mut in_map = false;
+            for (offset, line) in paragraph.iter().enumerate() {
+                if !is_map_row(line) {
+                    // Lines between two maps describe the earlier one.
+                    level.add_metadata(line);
+                    in_map = false;
+                    continue;
+                }
+                if !in_map && !level.text.is_empty() {
+                    collection.levels.push(std::mem::take(&mut level));
+                }
+                if level.text.is_empty() {
+                    level.line = first_line + offset + 1;
+                }
+                level.text.push_str(line.trim_end());
+                level.text.push('\n');
+                in_map = true;
+            }
+            collection.levels.push(level);
+        }
+
+        if collection.levels.is_empty() {
+            for line in pending.into_iter().flatten() {
+                collection.add_metadata(line);
+            }
+        }
+        collection
+    }
+
+    fn add_metadata(&mut self, line: &str) {
+        add_metadata(line, &mut self.title, &mut self.author, &mut self.comments);
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Collection, SolveError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|e| SolveError::Io {
+            path: path.display().to_string(),
+            message: e.to_string(),
+        })?;
+        Ok(Collection::parse(&text))
+    }
+
+    /// Finds a level by 1-based index or, failing that, by title (ignoring
+    /// case). Returns its 0-based index.
+    pub fn select(&self, selector: &str) -> Result<(usize, &Level), SolveError> {
+        let by_index = selector.parse::<usize>().ok().filter(|&n| n >= 1 && n <= self.levels.len()).map(|n| n - 1);
+        let index = by_index.or_else(|| {
+            self.levels
+                .iter()
+                .position(|level| level.title.as_deref().is_some_and(|t| t.eq_ignore_ascii_case(selector)))
+        });
+        match index {
+            Some(index) => Ok((index, &self.levels[index])),
+            None => Err(SolveError::NoSuchLevel {
+                selector: selector.to_string(),
+                levels: self.levels.len(),
+            }),
+        }
+    }
+}
@@ -0,0 +1,296 @@
+use rustc_hash::{FxHashMap, FxHashSet};
+use std::collections::VecDeque;
+
+use crate::learned::LearnedDeadlock;
+use crate::pattern::{DEAD, GOAL, WALL};
+use crate::puzzle::{BoxVec, Point};
+use crate::reach::Reachability;
+use crate::solver::DIR_OFFSETS;
+
+/// Reduced positions a corral search expands before giving up unproven.
+const SEARCH_LIMIT: usize = 16;
+
+/// Verdicts remembered before the cache starts over.
+const CACHE_LIMIT: usize = 1 << 16;
+
+/// Learned deadlocks kept at most; later proofs are still cached.
+const LEARNED_LIMIT: usize = 1 << 16;
+
+/// Corral deadlock detection.
+///
+/// A corral is an area of floor the player cannot reach; the boxes next to
+/// it fence it off. When every push the player can make on those boxes goes
+/// into the corral, the corral has to be dealt with from the inside sooner or
+/// later, so it is worth proving that it cannot be: the fence and corral
+/// boxes are searched on their own, every other box removed (which only
+/// makes things easier for the player). If no sequence of pushes gets them
+/// all onto goals or opens the corral up, the position is a deadlock. The
+/// search is kept small: most corral deadlocks are settled within a few
+/// pushes, and the rest are left to the main search.
+///
+/// Verdicts are cached by box set and player region, both of which fully
+/// determine the reduced search, so repeated fences cost one lookup. Each
+/// proof is also cut down to a minimal box set and learned: it then prunes
+/// any position holding those boxes with the player able to walk to its
+/// square, whatever the other boxes do.
+#[derive(Clone)]
+pub(crate) struct Corrals {
+    width: i16,
+    height: i16,
+    occupied: Vec<bool>,
+    seen: Vec<bool>,
+    inside: Vec<bool>,
+    reach: Reachability,
+    cache: FxHashMap<Box<[u16]>, bool>,
+    learned: Vec<LearnedDeadlock>,
+    /// Indices into `learned`, filed under the square of each one's first box.
+    anchored: Vec<Vec<u32>>,
+}
+
+impl Corrals {
+    pub(crate) fn new(width: i16, height: i16) -> Self {
+        let size = (width * height) as usize;
+        Corrals {
+            width,
+            height,
+            occupied: vec![false; size],
+            seen: vec![false; size],
+            inside: vec![false; size],
+            reach: Reachability::new(width, height),
+            cache: FxHashMap::default(),
+            learned: Vec::new(),
+            anchored: vec![Vec::new(); size],
+        }
+    }
+
+    #[inline(always)]
+    fn to_idx(&self, p: Point) -> usize {
+        (p.row * self.width + p.col) as usize
+    }
+
+    #[inline(always)]
+    fn neighbour(&self, p: Point, dir: usize) -> Option<Point> {
+        let (drow, dcol) = DIR_OFFSETS[dir];
+        let next = Point::new(p.row + drow as i16, p.col + dcol as i16);
+        (next.row >= 0 && next.row < self.height && next.col >= 0 && next.col < self.width).then_some(next)
+    }
+
+    pub(crate) fn learned(&self) -> &[LearnedDeadlock] {
+        &self.learned
+    }
+
+    /// Adds a deadlock unless it is known already. Squares off the map are
+    /// rejected, so a database entry for another map cannot do harm.
+    pub(crate) fn learn(&mut self, deadlock: &LearnedDeadlock) {
+        let on_map = |p: &Point| p.row >= 0 && p.row < self.height && p.col >= 0 && p.col < self.width;
+        if self.learned.len() >= LEARNED_LIMIT
+            || deadlock.boxes.is_empty()
+            || !on_map(&deadlock.player)
+            || !deadlock.boxes.iter().all(on_map)
+        {
+            return;
+        }
+        let mut deadlock = deadlock.clone();
+        deadlock.boxes.sort_unstable_by_key(|&b| self.to_idx(b));
+        let anchor = self.to_idx(deadlock.boxes[0]);
+        if self.anchored[anchor].iter().any(|&i| self.learned[i as usize] == deadlock) {
+            return;
+        }
+        self.anchored[anchor].push(self.learned.len() as u32);
+        self.learned.push(deadlock);
+    }
+
+    /// Whether a learned deadlock matches: all of its boxes are present and
+    /// the player can walk to its square.
+    pub(crate) fn is_learned_deadlock(&mut self, boxes: &[Point], reach: &Reachability) -> bool {
+        if self.learned.is_empty() {
+            return false;
+        }
+        for &b in boxes {
+            let idx = self.to_idx(b);
+            self.occupied[idx] = true;
+        }
+        let found = boxes.iter().any(|&b| {
+            self.anchored[self.to_idx(b)].iter().any(|&i| {
+                let deadlock = &self.learned[i as usize];
+                reach.is_reachable(deadlock.player) && deadlock.boxes.iter().all(|&p| self.occupied[self.to_idx(p)])
+            })
+        });
+        for &b in boxes {
+            let idx = self.to_idx(b);
+            self.occupied[idx] = false;
+        }
+        found
+    }
+
+    /// Whether some corral of the position is proven a deadlock. `reach`
+    /// must hold the player's region for `boxes`; `squares` are the
+    /// empty-board pattern flags (see `pattern::Area`).
+    pub(crate) fn is_deadlock(&mut self, map: &[u8], squares: &[u8], boxes: &[Point], reach: &Reachability) -> bool {
+        for &b in boxes {
+            let idx = self.to_idx(b);
+            self.occupied[idx] = true;
+        }
+        self.seen.fill(false);
+
+        let mut deadlock = false;
+        for start in 0..squares.len() {
+            let p = Point::new((start / self.width as usize) as i16, (start % self.width as usize) as i16);
+            if squares[start] == WALL || self.occupied[start] || self.seen[start] || reach.is_reachable(p) {
+                continue;
+            }
+            // With every box in the fence there is nothing to remove, and the
+            // sub-search would only repeat the main one.
+            let (area, fence) = self.flood_corral(squares, reach, p);
+            if fence.len() < boxes.len() && self.is_proven(map, squares, reach, &area, fence) {
+                deadlock = true;
+                break;
+            }
+        }
+
+        for &b in boxes {
+            let idx = self.to_idx(b);
+            self.occupied[idx] = false;
+        }
+        deadlock
+    }
+
+    /// The corral around `start`: unreachable floor connected to it, and the
+    /// boxes next to that floor. Areas that share a box count as one, since
+    /// pushing that box changes both. All of it is marked in `seen`.
+    fn flood_corral(&mut self, squares: &[u8], reach: &Reachability, start: Point) -> (Vec<Point>, BoxVec) {
+        let mut queue = vec![start];
+        let start_idx = self.to_idx(start);
+        self.seen[start_idx] = true;
+        let mut head = 0;
+        while head < queue.len() {
+            let current = queue[head];
+            head += 1;
+            let from_box = self.occupied[self.to_idx(current)];
+            for dir in 0..4 {
+                let Some(next) = self.neighbour(current, dir) else { continue };
+                let idx = self.to_idx(next);
+                // Boxes are crossed one at a time, never from box to box.
+                let box_to_box = from_box && self.occupied[idx];
+                if squares[idx] != WALL && !self.seen[idx] && !reach.is_reachable(next) && !box_to_box {
+                    self.seen[idx] = true;
+                    queue.push(next);
+                }
+            }
+        }
+        let (fence, area): (Vec<Point>, Vec<Point>) = queue.into_iter().partition(|&p| self.occupied[self.to_idx(p)]);
+        (area, fence.into_iter().collect())
+    }
+
+    /// Whether the corral is forced and its boxes, searched alone, cannot
+    /// get out of it. Verdicts come from the cache when they can.
+    fn is_proven(&mut self, map: &[u8], squares: &[u8], reach: &Reachability, area: &[Point], fence: BoxVec) -> bool {
+        for &p in area {
+            let idx = self.to_idx(p);
+            self.inside[idx] = true;
+        }
+
+        // Whether every push available on the corral's boxes leads inside.
+        let mut forced = true;
+        for &b in &fence {
+            for dir in 0..4 {
+                let (Some(origin), Some(target)) = (self.neighbour(b, dir ^ 1), self.neighbour(b, dir)) else {
+                    continue;
+                };
+                let target_idx = self.to_idx(target);
+                if reach.is_reachable(origin)
+                    && squares[target_idx] & (WALL | DEAD) == 0
+                    && !self.occupied[target_idx]
+                    && !self.inside[target_idx]
+                {
+                    forced = false;
+                }
+            }
+        }
+        let needs_work = fence.iter().any(|&b| squares[self.to_idx(b)] & GOAL == 0);
+
+        let proven = forced && needs_work && {
+            let mut key: Vec<u16> = fence.iter().map(|&b| self.to_idx(b) as u16).collect();
+            key.sort_unstable();
+            key.push(self.to_idx(reach.top_left()) as u16);
+            match self.cache.get(key.as_slice()) {
+                Some(&proven) => proven,
+                None => {
+                    let player = reach.top_left();
+                    let proven = self.search(map, squares, fence.clone(), player, area);
+                    if proven {
+                        let boxes = self.minimise(map, squares, fence, player, area).to_vec();
+                        self.learn(&LearnedDeadlock { player, boxes });
+                    }
+                    if self.cache.len() >= CACHE_LIMIT {
+                        self.cache.clear();
+                    }
+                    self.cache.insert(key.into_boxed_slice(), proven);
+                    proven
+                }
+            }
+        };
+
+        for &p in area {
+            let idx = self.to_idx(p);
+            self.inside[idx] = false;
+        }
+        proven
+    }
+
+    /// Drops boxes from a proven set, one at a time, for as long as the
+    /// proof still holds without them.
+    fn minimise(&mut self, map: &[u8], squares: &[u8], mut boxes: BoxVec, player: Point, area: &[Point]) -> BoxVec {
+        let mut i = 0;
+        while i < boxes.len() && boxes.len() > 1 {
+            let mut fewer = boxes.clone();
+            fewer.remove(i);
+            if self.search(map, squares, fewer.clone(), player, area) {
+                boxes = fewer;
+            } else {
+                i += 1;
+            }
+        }
+        boxes
+    }
+
+    /// Breadth-first search over the corral boxes alone. True only when it
+    /// runs out of positions, none of them solved or with the corral open.
+    fn search(&mut self, map: &[u8], squares: &[u8], boxes: BoxVec, player: Point, area: &[Point]) -> bool {
+        let mut queue = VecDeque::from([(boxes, player)]);
+        let mut visited: FxHashSet<Vec<u16>> = FxHashSet::default();
+        while let Some((boxes, player)) = queue.pop_front() {
+            self.reach.compute(map, &boxes, player);
+            let mut key: Vec<u16> = boxes.iter().map(|&b| self.to_idx(b) as u16).collect();
+            key.sort_unstable();
+            key.push(self.to_idx(self.reach.top_left()) as u16);
+            if !visited.insert(key) {
+                continue;
+            }
+            if visited.len() > SEARCH_LIMIT
+                || boxes.iter().all(|&b| squares[self.to_idx(b)] & GOAL != 0)
+                || area.iter().all(|&p| self.reach.is_reachable(p) || boxes.contains(&p))
+            {
+                return false;
+            }
+
+            for (i, &b) in boxes.iter().enumerate() {
+                for dir in 0..4 {
+                    let (Some(origin), Some(target)) = (self.neighbour(b, dir ^ 1), self.neighbour(b, dir)) else {
+                        continue;
+                    };
+                    if !self.reach.is_reachable(origin)
+                        || squares[self.to_idx(target)] & (WALL | DEAD) != 0
+                        || boxes.contains(&target)
+                    {
+                        continue;
+                    }
+                    let mut next = boxes.clone();
+                    next[i] = target;
+                    queue.push_back((next, b));
+                }
+            }
+        }
+        true
+    }
+}
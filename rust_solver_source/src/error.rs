@@ -0,0 +1,80 @@
+use std::fmt;
+
+use crate::solver::Partial;
+use crate::validate::Diagnostic;
+
+/// Reasons a solve can fail.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SolveError {
+    /// The puzzle file could not be read.
+    Io { path: String, message: String },
+    /// The puzzle text is malformed. `line` and `column` are 1-based.
+    Parse { line: usize, column: usize, message: String },
+    /// The puzzle parsed but cannot be searched; every problem found.
+    InvalidPuzzle(Vec<Diagnostic>),
+    /// No level of a collection matches the requested index or title.
+    NoSuchLevel { selector: String, levels: usize },
+    /// The search space was exhausted without reaching a solved state.
+    Unsolvable,
+    /// The configured time limit expired before a solution was found.
+    Timeout(Box<Partial>),
+    /// The configured node limit was reached before a solution was found.
+    NodeLimit(Box<Partial>),
+    /// The configured memory limit was reached before a solution was found.
+    OutOfMemory(Box<Partial>),
+    /// The search was cancelled by the caller.
+    Cancelled,
+}
+
+impl fmt::Display for SolveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SolveError::Io { path, message } => write!(f, "cannot read {}: {}", path, message),
+            SolveError::Parse { line, column, message } => {
+                write!(f, "parse error at line {}, column {}: {}", line, column, message)
+            }
+            SolveError::InvalidPuzzle(problems) => {
+                write!(f, "invalid puzzle")?;
+                for problem in problems {
+                    write!(f, "\n  {}", problem)?;
+                }
+                Ok(())
+            }
+            SolveError::NoSuchLevel { selector, levels } => {
+                write!(f, "no level '{}' (the file has {} levels)", selector, levels)
+            }
+            SolveError::Unsolvable => write!(f, "no solution exists"),
+            SolveError::Timeout(_) => write!(f, "time limit reached"),
+            SolveError::NodeLimit(_) => write!(f, "node limit reached"),
+            SolveError::OutOfMemory(_) => write!(f, "memory limit reached"),
+            SolveError::Cancelled => write!(f, "search cancelled"),
+        }
+    }
+}
+
+impl SolveError {
+    /// Short stable identifier, for machine-readable output.
+    pub fn name(&self) -> &'static str {
+        match self {
+            SolveError::Io { .. } => "io",
+            SolveError::Parse { .. } => "parse",
+            SolveError::InvalidPuzzle(_) => "invalid-puzzle",
+            SolveError::NoSuchLevel { .. } => "no-such-level",
+            SolveError::Unsolvable => "unsolvable",
+            SolveError::Timeout(_) => "timeout",
+            SolveError::NodeLimit(_) => "node-limit",
+            SolveError::OutOfMemory(_) => "out-of-memory",
+            SolveError::Cancelled => "cancelled",
+        }
+    }
+
+    /// Progress made before a limit stopped the search.
+    pub fn partial(&self) -> Option<&Partial> {
+        match self {
+            SolveError::Timeout(p) | SolveError::NodeLimit(p) | SolveError::OutOfMemory(p) => Some(p),
+            _ => None,
+        }
+    }
+}
+
+impl std::error::Error for SolveError {}
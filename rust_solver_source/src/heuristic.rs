@@ -0,0 +1,206 @@
+use smallvec::SmallVec;
+use std::collections::VecDeque;
+
+use crate::puzzle::Point;
+use crate::solver::DIR_OFFSETS;
+
+/// Distance value for squares from which a goal cannot be reached.
+pub(crate) const UNREACHABLE: u16 = u16::MAX;
+
+/// Cost used for a box/goal pair with no push path. Larger than any sum of
+/// 64 real push distances, so an assignment using one is worse than every
+/// assignment that does not.
+const INFEASIBLE: i32 = 1 << 24;
+
+/// Box heuristic used by the search.
+///
+/// `Matching` assigns boxes to goals by a minimum-cost perfect matching over
+/// true push distances; it is admissible and consistent. `Greedy` gives each
+/// box the nearest unused goal by Manhattan distance in box order, which is
+/// cheaper per node but order-dependent.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Heuristic {
+    Greedy,
+    #[default]
+    Matching,
+}
+
+impl Heuristic {
+    pub const NAMES: [&'static str; 2] = ["greedy", "matching"];
+
+    pub fn from_name(name: &str) -> Option<Heuristic> {
+        match name {
+            "greedy" => Some(Heuristic::Greedy),
+            "matching" => Some(Heuristic::Matching),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Heuristic::Greedy => "greedy",
+            Heuristic::Matching => "matching",
+        }
+    }
+}
+
+/// Minimum number of pushes to bring a box from each square to each goal,
+/// with walls respected and other boxes ignored.
+#[derive(Clone)]
+pub(crate) struct PushDistances {
+    size: usize,
+    dist: Vec<u16>,
+}
+
+impl PushDistances {
+    /// Reverse BFS from every goal: a box at `c` can be pushed to `c + d`
+    /// when both `c + d` and the player square `c - d` are not walls.
+    pub(crate) fn new(width: i16, height: i16, map: &[u8], goals: &[Point]) -> Self {
+        let size = (width * height) as usize;
+        let mut dist = vec![UNREACHABLE; size * goals.len()];
+        let mut queue = VecDeque::with_capacity(size);
+        let is_open = |row: i16, col: i16| {
+            row >= 0 && row < height && col >= 0 && col < width && map[(row * width + col) as usize] != 1
+        };
+
+        for (g, &goal) in goals.iter().enumerate() {
+            let table = &mut dist[g * size..(g + 1) * size];
+            table[(goal.row * width + goal.col) as usize] = 0;
+            queue.clear();
+            queue.push_back(goal);
+
+            while let Some(target) = queue.pop_front() {
+                let next_dist = table[(target.row * width + target.col) as usize] + 1;
+                for &(drow, dcol) in &DIR_OFFSETS {
+                    let from_row = target.row - drow as i16;
+                    let from_col = target.col - dcol as i16;
+                    let player_row = from_row - drow as i16;
+                    let player_col = from_col - dcol as i16;
+
+                    if !is_open(from_row, from_col) || !is_open(player_row, player_col) {
+                        continue;
+                    }
+                    let from_idx = (from_row * width + from_col) as usize;
+                    if table[from_idx] == UNREACHABLE {
+                        table[from_idx] = next_dist;
+                        queue.push_back(Point::new(from_row, from_col));
+                    }
+                }
+            }
+        }
+
+        PushDistances { size, dist }
+    }
+
+    #[inline(always)]
+    pub(crate) fn get(&self, goal: usize, cell: usize) -> u16 {
+        self.dist[goal * self.size + cell]
+    }
+
+    /// Fewest pushes from `cell` to any goal, or `UNREACHABLE`.
+    #[inline(always)]
+    pub(crate) fn nearest(&self, goal_count: usize, cell: usize) -> u16 {
+        (0..goal_count).map(|g| self.get(g, cell)).min().unwrap_or(UNREACHABLE)
+    }
+}
+
+/// Whether every row can take a distinct column, `allowed[i]` being the
+/// bitmask of columns row `i` may take (augmenting paths, at most 64
+/// columns).
+pub(crate) fn has_perfect_matching(allowed: &[u64]) -> bool {
+    let mut owner = [usize::MAX; 64];
+    (0..allowed.len()).all(|row| augment(allowed, row, &mut 0, &mut owner))
+}
+
+fn augment(allowed: &[u64], row: usize, visited: &mut u64, owner: &mut [usize; 64]) -> bool {
+    let mut columns = allowed[row];
+    while columns != 0 {
+        let col = columns.trailing_zeros() as usize;
+        columns &= columns - 1;
+        if *visited & (1u64 << col) != 0 {
+            continue;
+        }
+        *visited |= 1u64 << col;
+        if owner[col] == usize::MAX || augment(allowed, owner[col], visited, owner) {
+            owner[col] = row;
+            return true;
+        }
+    }
+    false
+}
+
+/// Cost of a minimum-cost perfect matching between `n` rows and `n` columns
+/// (Hungarian algorithm with potentials, O(n^3)). `cost` returns `None` for
+/// pairs that cannot be matched; the result is `None` if every perfect
+/// matching needs such a pair.
+pub(crate) fn min_cost_matching(n: usize, cost: impl Fn(usize, usize) -> Option<i32>) -> Option<i32> {
+    type Row = SmallVec<[i32; 32]>;
+    let mut u: Row = SmallVec::from_elem(0, n + 1);
+    let mut v: Row = SmallVec::from_elem(0, n + 1);
+    let mut minv: Row = SmallVec::from_elem(0, n + 1);
+    let mut p: SmallVec<[usize; 32]> = SmallVec::from_elem(0, n + 1);
+    let mut way: SmallVec<[usize; 32]> = SmallVec::from_elem(0, n + 1);
+    let mut used: SmallVec<[bool; 32]> = SmallVec::from_elem(false, n + 1);
+
+    for i in 1..=n {
+        p[0] = i;
+        let mut j0 = 0;
+        minv.iter_mut().for_each(|m| *m = i32::MAX);
+        used.iter_mut().for_each(|u| *u = false);
+
+        loop {
+            used[j0] = true;
+            let i0 = p[j0];
+            let mut delta = i32::MAX;
+            let mut j1 = 0;
+
+            for j in 1..=n {
+                if used[j] {
+                    continue;
+                }
+                let c = cost(i0 - 1, j - 1).unwrap_or(INFEASIBLE);
+                let cur = c - u[i0] - v[j];
+                if cur < minv[j] {
+                    minv[j] = cur;
+                    way[j] = j0;
+                }
+                if minv[j] < delta {
+                    delta = minv[j];
+                    j1 = j;
+                }
+            }
+
+            for j in 0..=n {
+                if used[j] {
+                    u[p[j]] += delta;
+                    v[j] -= delta;
+                } else {
+                    minv[j] -= delta;
+                }
+            }
+
+            j0 = j1;
+            if p[j0] == 0 {
+                break;
+            }
+        }
+
+        loop {
+            let j1 = way[j0];
+            p[j0] = p[j1];
+            j0 = j1;
+            if j0 == 0 {
+                break;
+            }
+        }
+    }
+
+    let mut total = 0;
+    for j in 1..=n {
+        match cost(p[j] - 1, j - 1) {
+            Some(c) => total += c,
+            None => return None,
+        }
+    }
+    Some(total)
+}
@@ -0,0 +1,150 @@
+use rustc_hash::FxHashSet;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+use crate::error::SolveError;
+use crate::puzzle::{Point, Puzzle};
+
+/// A deadlock proven by the search: with boxes on all of `boxes`, wherever
+/// the others are, the puzzle cannot be solved while the player can walk to
+/// `player`. The box set is minimal: without any one of them the proof fails.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct LearnedDeadlock {
+    pub player: Point,
+    /// Sorted row by row.
+    pub boxes: Vec<Point>,
+}
+
+impl LearnedDeadlock {
+    /// The puzzle's walls and goals with this deadlock's boxes and player,
+    /// in standard notation.
+    pub fn to_text(&self, puzzle: &Puzzle) -> String {
+        puzzle.text_with(&self.boxes, self.player)
+    }
+}
+
+/// Learned deadlocks of many levels, kept in a file between runs.
+///
+/// Levels are keyed by [`Puzzle::layout_hash`], so a level with the same
+/// walls and goals but another start position shares its deadlocks. The
+/// file has one deadlock per line: the layout hash in hex, then the player
+/// and box squares as 0-based `row,col`; lines starting with `;` are
+/// comments.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DeadlockDb {
+    levels: BTreeMap<u64, Vec<LearnedDeadlock>>,
+}
+
+impl DeadlockDb {
+    pub fn new() -> DeadlockDb {
+        DeadlockDb::default()
+    }
+
+    pub fn parse(text: &str) -> Result<DeadlockDb, SolveError> {
+        let mut db = DeadlockDb::new();
+        for (i, line) in text.lines().enumerate() {
+            let error = |column: usize, message: String| SolveError::Parse { line: i + 1, column, message };
+            let line = line.trim_end();
+            if line.is_empty() || line.starts_with(';') {
+                continue;
+            }
+            // Fields with their 1-based columns, however much space is between.
+            let fields: Vec<(usize, &str)> = line
+                .split_whitespace()
+                .map(|field| (field.as_ptr() as usize - line.as_ptr() as usize + 1, field))
+                .collect();
+            let key = u64::from_str_radix(fields[0].1, 16)
+                .map_err(|_| error(1, format!("invalid layout hash '{}'", fields[0].1)))?;
+            let mut squares = Vec::with_capacity(fields.len() - 1);
+            for &(column, field) in &fields[1..] {
+                let square = field
+                    .split_once(',')
+                    .and_then(|(row, col)| Some(Point::new(row.parse().ok()?, col.parse().ok()?)))
+                    .ok_or_else(|| error(column, format!("invalid square '{}'", field)))?;
+                squares.push(square);
+            }
+            if squares.len() < 2 {
+                return Err(error(1, "a deadlock needs a player square and at least one box".to_string()));
+            }
+            let player = squares.remove(0);
+            db.levels.entry(key).or_default().push(LearnedDeadlock { player, boxes: squares });
+        }
+        Ok(db)
+    }
+
+    /// Reads a database file; one that does not exist yet is empty.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<DeadlockDb, SolveError> {
+        let path = path.as_ref();
+        match fs::read_to_string(path) {
+            Ok(text) => DeadlockDb::parse(&text),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(DeadlockDb::new()),
+            Err(e) => Err(SolveError::Io { path: path.display().to_string(), message: e.to_string() }),
+        }
+    }
+
+    /// Writes the database, replacing the file only once it is complete.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), SolveError> {
+        let path = path.as_ref();
+        let io_error = |e: std::io::Error| SolveError::Io { path: path.display().to_string(), message: e.to_string() };
+        let partial = path.with_extension("tmp");
+        fs::write(&partial, self.to_text()).map_err(io_error)?;
+        fs::rename(&partial, path).map_err(io_error)
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = String::from("; rust_solver learned deadlocks: layout hash, player, boxes\n");
+        for (key, deadlocks) in &self.levels {
+            for deadlock in deadlocks {
+                text.push_str(&format!("{:016x}", key));
+                for square in std::iter::once(&deadlock.player).chain(&deadlock.boxes) {
+                    text.push_str(&format!(" {},{}", square.row, square.col));
+                }
+                text.push('\n');
+            }
+        }
+        text
+    }
+
+    /// Deadlocks learned on levels laid out like `puzzle`.
+    pub fn deadlocks(&self, puzzle: &Puzzle) -> &[LearnedDeadlock] {
+        self.levels.get(&puzzle.layout_hash()).map_or(&[], Vec::as_slice)
+    }
+
+    /// Adds the deadlocks not known yet and returns how many that was.
+    pub fn record(&mut self, puzzle: &Puzzle, deadlocks: &[LearnedDeadlock]) -> usize {
+        if deadlocks.is_empty() {
+            return 0;
+        }
+        let known = self.levels.entry(puzzle.layout_hash()).or_default();
+        let mut seen: FxHashSet<LearnedDeadlock> = known.iter().cloned().collect();
+        let before = known.len();
+        known.extend(deadlocks.iter().filter(|d| seen.insert((*d).clone())).cloned());
+        known.len() - before
+    }
+
+    /// Forgets the deadlocks of levels laid out like `puzzle` and returns
+    /// how many there were.
+    pub fn remove(&mut self, puzzle: &Puzzle) -> usize {
+        self.levels.remove(&puzzle.layout_hash()).map_or(0, |deadlocks| deadlocks.len())
+    }
+
+    pub fn clear(&mut self) {
+        self.levels.clear();
+    }
+
+    /// Layout hashes with their deadlock counts, in hash order.
+    pub fn levels(&self) -> impl Iterator<Item = (u64, usize)> + '_ {
+        self.levels.iter().map(|(&key, deadlocks)| (key, deadlocks.len()))
+    }
+
+    /// Deadlocks over all levels.
+    pub fn len(&self) -> usize {
+        self.levels.values().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.levels.is_empty()
+    }
+}
@@ -0,0 +1,43 @@
+//! Sokoban solver library.
+//!
+//! Parse a level with [`Puzzle::parse`], then search it with [`Solver`] (or the
+//! one-shot [`solve`] helper):
+//!
+//! ```
+//! use rust_solver::{Puzzle, SolveOptions, Solver};
+//!
+//! let puzzle = Puzzle::parse("#####\n#@$.#\n#####")?;
+//! let solution = Solver::new(&puzzle).solve(&SolveOptions::default())?;
+//! assert_eq!(solution.moves, "R");
+//! # Ok::<(), rust_solver::SolveError>(())
+//! ```
+
+mod closed;
+mod collection;
+mod corral;
+mod error;
+mod heuristic;
+mod learned;
+mod moves;
+mod pattern;
+mod portfolio;
+mod puzzle;
+mod reach;
+mod solver;
+mod validate;
+mod verify;
+
+pub use closed::ClosedSetMode;
+pub use collection::{Collection, Level};
+pub use error::SolveError;
+pub use heuristic::Heuristic;
+pub use learned::{DeadlockDb, LearnedDeadlock};
+pub use moves::{to_lurd, Direction, Move};
+pub use pattern::Patterns;
+pub use puzzle::{Point, Puzzle};
+pub use solver::{
+    solve, solve_str, Objective, Partial, Progress, Solution, SolveOptions, SolveStats, Solver, DEFAULT_SEED,
+    DEFAULT_TT_SIZE,
+};
+pub use validate::Diagnostic;
+pub use verify::{verify, IllegalMove, IllegalReason, Replay};
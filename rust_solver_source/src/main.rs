@@ -1,621 +1,614 @@
-use rustc_hash::FxHashSet;
-use smallvec::SmallVec;
-use arrayvec::ArrayVec;
-use std::cmp::Ordering;
-use std::collections::BinaryHeap;
+use parking_lot::Mutex;
+use rust_solver::{
+    verify, ClosedSetMode, Collection, DeadlockDb, Heuristic, Level, Objective, Patterns, Puzzle, Solution, SolveError,
+    SolveOptions, SolveStats, Solver,
+};
+use serde_json::{json, Value};
 use std::env;
 use std::fs;
-
-// Direction constants
-const DIR_UP: u8 = 0;
-const DIR_DOWN: u8 = 1;
-const DIR_LEFT: u8 = 2;
-const DIR_RIGHT: u8 = 3;
-
-const DIR_OFFSETS: [(i8, i8); 4] = [(-1, 0), (1, 0), (0, -1), (0, 1)];
-const DIR_CHARS: [char; 4] = ['u', 'd', 'l', 'r'];
-
-const ROTATION_PATTERNS: [[usize; 9]; 4] = [
-    [0, 1, 2, 3, 4, 5, 6, 7, 8],
-    [2, 5, 8, 1, 4, 7, 0, 3, 6],
-    [8, 7, 6, 5, 4, 3, 2, 1, 0],
-    [6, 3, 0, 7, 4, 1, 8, 5, 2],
-];
-
-// Compact point using i16 for better cache performance
-#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
-#[repr(C)]
-struct Point {
-    row: i16,
-    col: i16,
+use std::io::{self, Read};
+use std::process;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+mod batch;
+mod serve;
+
+// Process exit codes. 0 is success; anything else means no solution was printed.
+const EXIT_USAGE: i32 = 1;
+const EXIT_IO: i32 = 2;
+const EXIT_PARSE: i32 = 3;
+const EXIT_INVALID: i32 = 4;
+const EXIT_UNSOLVABLE: i32 = 5;
+const EXIT_TIMEOUT: i32 = 6;
+const EXIT_OUT_OF_MEMORY: i32 = 7;
+const EXIT_CANCELLED: i32 = 8;
+const EXIT_NODE_LIMIT: i32 = 9;
+const EXIT_REJECTED: i32 = 10;
+
+/// Puzzle path that reads the puzzle from standard input.
+const STDIN_PATH: &str = "-";
+
+fn exit_code(err: &SolveError) -> i32 {
+    match err {
+        SolveError::Io { .. } => EXIT_IO,
+        SolveError::Parse { .. } => EXIT_PARSE,
+        SolveError::InvalidPuzzle(_) => EXIT_INVALID,
+        SolveError::NoSuchLevel { .. } => EXIT_USAGE,
+        SolveError::Unsolvable => EXIT_UNSOLVABLE,
+        SolveError::Timeout(_) => EXIT_TIMEOUT,
+        SolveError::NodeLimit(_) => EXIT_NODE_LIMIT,
+        SolveError::OutOfMemory(_) => EXIT_OUT_OF_MEMORY,
+        SolveError::Cancelled => EXIT_CANCELLED,
+    }
 }
 
-impl Point {
-    #[inline(always)]
-    const fn new(row: i16, col: i16) -> Self {
-        Point { row, col }
-    }
+const USAGE: &str = "Usage: rust_solver [options] <puzzle_file>
+       rust_solver verify [--level <n|title>] <puzzle_file> <solution>
+       rust_solver export [--rle] [--level <n|title>] <puzzle_file>
+       rust_solver batch [options] [--jobs <n>] [--report <file>] <directory|collection>
+       rust_solver serve
+       rust_solver deadlocks [--clear] [--level <n|title>] <db_file> [<puzzle_file>]
+
+`verify` replays a LURD solution, given inline or as a file, and reports
+move and push counts and whether every box ends on a goal (exit code 10 if
+a move is illegal or the puzzle is left unsolved).
+
+`export` prints the puzzle in plain notation, or as one run-length-encoded
+line with --rle.
+
+`batch` solves every level of a directory (.txt, .sok and .xsb files) or of a
+multi-level collection file with the options below (but not --level,
+--lowercase or --format), 10 seconds per level unless --time-limit says
+otherwise, and prints a summary table. --jobs runs
+that many levels at once; --report writes every result to a .json file, or
+CSV for any other extension.
+
+`serve` keeps running and answers JSON requests, one per line on stdin
+(solve, verify, cancel, stats), with JSON lines on stdout: progress events
+while a solve runs, then its result.
+
+`deadlocks` lists how many learned deadlocks a --deadlock-db file holds per
+level, or draws those of one puzzle. With --clear it forgets them instead,
+for that puzzle or for every level.
+
+A puzzle file may be a plain map or a .sok/.xsb collection of levels; `-`
+reads it from standard input.
+
+Options:
+  --level <n|title>
+        Level of a collection file to use, by 1-based number or title.
+        Without it a collection's first level is used.
+  --objective <any|moves|pushes|pushes-then-moves|moves-then-pushes>
+        What to minimize. `any` (default) is fast greedy search; the others
+        run A* and return an optimal solution for that objective.
+  --heuristic <matching|greedy>
+        Box-to-goal estimate. `matching` (default) is a minimum-cost
+        assignment over push distances; `greedy` is the older nearest-goal
+        estimate, cheaper per node.
+  --tt-size <entries>
+        Heuristic cache size (default 1048576, 16 bytes per entry; 0 disables).
+  --closed-set <exact|hash>
+        `exact` (default) stores full states and can never prune a state by
+        mistake; `hash` stores only 64-bit hashes, using less memory.
+  --time-limit <seconds>
+        Give up after this much wall-clock time (exit code 6).
+  --max-nodes <n>
+        Give up after expanding this many states (exit code 9).
+  --max-memory <megabytes>
+        Give up once the search tables reach about this size (exit code 7).
+  --patterns <file|builtin|none>
+        Deadlock patterns checked around every pushed box: the built-in
+        library (default), none, or one loaded from a file in the format of
+        patterns.txt.
+  --deadlock-db <file>
+        Deadlocks learned on earlier runs of levels with the same walls and
+        goals are read from this file and used to prune; the ones this run
+        proves are added to it. The file is created when missing.
+  --seed <n>
+        Seed for the Zobrist hash keys. Runs with the same seed produce
+        identical output; the default is fixed.
+  --threads <n>
+        Run up to n differently configured searches in parallel (greedy,
+        weighted A*, other heuristic and tie-break) and print whichever
+        solution comes first. 1 (default) is a single reproducible search.
+  --lowercase
+        Print every move in lowercase instead of standard LURD, where pushes
+        are uppercase (`uUdDlLrR`).
+  --format <text|json>
+        `text` (default) prints the solution alone; `json` prints one object
+        with the solution, search statistics, outcome and configuration,
+        also when the search fails.";
+
+/// How the result is printed.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Text,
+    Json,
+}
 
-    #[inline(always)]
-    fn pack(self) -> u32 {
-        ((self.row as u32) << 16) | (self.col as u32 & 0xFFFF)
-    }
+impl Format {
+    const NAMES: [&'static str; 2] = ["text", "json"];
 
-    #[inline(always)]
-    fn unpack(packed: u32) -> Self {
-        Point {
-            row: (packed >> 16) as i16,
-            col: (packed & 0xFFFF) as i16,
+    fn from_name(name: &str) -> Option<Format> {
+        match name {
+            "text" => Some(Format::Text),
+            "json" => Some(Format::Json),
+            _ => None,
         }
     }
 }
 
-type BoxVec = SmallVec<[Point; 20]>;
-
-struct State {
-    boxes: BoxVec,
-    player: Point,
-    path: SmallVec<[u8; 256]>,
-    heuristic: i32,
-    hash: u64,
+struct Args {
+    puzzle_path: String,
+    options: SolveOptions,
+    format: Format,
+    lowercase: bool,
+    level: Option<String>,
+    deadlock_db: Option<String>,
 }
 
-impl Eq for State {}
-impl PartialEq for State {
-    #[inline(always)]
-    fn eq(&self, other: &Self) -> bool {
-        self.heuristic == other.heuristic
+/// `builtin`, `none`, or a pattern file.
+fn load_patterns(value: &str) -> Result<Arc<Patterns>, SolveError> {
+    match value {
+        "builtin" => Ok(Patterns::builtin()),
+        "none" => Ok(Arc::new(Patterns::none())),
+        path => Patterns::load(path).map(Arc::new),
     }
 }
 
-impl Ord for State {
-    #[inline(always)]
-    fn cmp(&self, other: &Self) -> Ordering {
-        other.heuristic.cmp(&self.heuristic)
-            .then_with(|| other.path.len().cmp(&self.path.len()))
+fn parse_args(args: &[String]) -> Result<Args, String> {
+    let mut puzzle_path = None;
+    let mut options = SolveOptions::default();
+    let mut format = Format::Text;
+    let mut lowercase = false;
+    let mut level = None;
+    let mut deadlock_db = None;
+    let mut iter = args.iter();
+
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--objective" => {
+                let value = iter.next().ok_or("--objective needs a value")?;
+                options.objective = Objective::from_name(value).ok_or_else(|| {
+                    format!("unknown objective '{}' (expected one of: {})", value, Objective::NAMES.join(", "))
+                })?;
+            }
+            "--heuristic" => {
+                let value = iter.next().ok_or("--heuristic needs a value")?;
+                options.heuristic = Heuristic::from_name(value).ok_or_else(|| {
+                    format!("unknown heuristic '{}' (expected one of: {})", value, Heuristic::NAMES.join(", "))
+                })?;
+            }
+            "--tt-size" => {
+                let value = iter.next().ok_or("--tt-size needs a value")?;
+                options.tt_size = value.parse().map_err(|_| format!("invalid --tt-size '{}'", value))?;
+            }
+            "--closed-set" => {
+                let value = iter.next().ok_or("--closed-set needs a value")?;
+                options.closed_set = ClosedSetMode::from_name(value).ok_or_else(|| {
+                    format!("unknown closed-set mode '{}' (expected one of: {})", value, ClosedSetMode::NAMES.join(", "))
+                })?;
+            }
+            "--time-limit" => {
+                let value = iter.next().ok_or("--time-limit needs a value")?;
+                let seconds: f64 = value.parse().map_err(|_| format!("invalid --time-limit '{}'", value))?;
+                options.time_limit = Some(
+                    Duration::try_from_secs_f64(seconds).map_err(|_| format!("invalid --time-limit '{}'", value))?,
+                );
+            }
+            "--max-nodes" => {
+                let value = iter.next().ok_or("--max-nodes needs a value")?;
+                options.max_nodes = Some(value.parse().map_err(|_| format!("invalid --max-nodes '{}'", value))?);
+            }
+            "--max-memory" => {
+                let value = iter.next().ok_or("--max-memory needs a value")?;
+                let megabytes: usize = value.parse().map_err(|_| format!("invalid --max-memory '{}'", value))?;
+                options.max_memory = Some(megabytes.saturating_mul(1 << 20));
+            }
+            "--patterns" => {
+                let value = iter.next().ok_or("--patterns needs a value")?;
+                options.patterns = load_patterns(value).map_err(|err| err.to_string())?;
+            }
+            "--seed" => {
+                let value = iter.next().ok_or("--seed needs a value")?;
+                options.seed = value.parse().map_err(|_| format!("invalid --seed '{}'", value))?;
+            }
+            "--threads" => {
+                let value = iter.next().ok_or("--threads needs a value")?;
+                options.threads = value
+                    .parse()
+                    .ok()
+                    .filter(|&n| n > 0)
+                    .ok_or_else(|| format!("invalid --threads '{}'", value))?;
+            }
+            "--format" => {
+                let value = iter.next().ok_or("--format needs a value")?;
+                format = Format::from_name(value).ok_or_else(|| {
+                    format!("unknown format '{}' (expected one of: {})", value, Format::NAMES.join(", "))
+                })?;
+            }
+            "--lowercase" => lowercase = true,
+            "--level" => level = Some(iter.next().ok_or("--level needs a value")?.clone()),
+            "--deadlock-db" => deadlock_db = Some(iter.next().ok_or("--deadlock-db needs a value")?.clone()),
+            flag if flag.starts_with("--") => return Err(format!("unknown option '{}'", flag)),
+            path if puzzle_path.is_none() => puzzle_path = Some(path.to_string()),
+            extra => return Err(format!("unexpected argument '{}'", extra)),
+        }
     }
-}
 
-impl PartialOrd for State {
-    #[inline(always)]
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
-    }
+    Ok(Args {
+        puzzle_path: puzzle_path.ok_or("missing puzzle file")?,
+        options,
+        format,
+        lowercase,
+        level,
+        deadlock_db,
+    })
 }
 
-struct TranspositionTable {
-    entries: Vec<(u64, i32, u8)>,
-    size: usize,
-    age: u8,
+/// Outcome of one run. `stats` is missing when no search was started.
+struct Report {
+    result: Result<Solution, SolveError>,
+    stats: Option<SolveStats>,
+    elapsed: Duration,
 }
 
-impl TranspositionTable {
-    fn new(size: usize) -> Self {
-        TranspositionTable {
-            entries: vec![(0, 0, 0); size],
-            size,
-            age: 0,
-        }
+/// Solves an already loaded (or failed) puzzle, starting from and adding to
+/// the learned deadlocks in `db`. The elapsed time covers precomputation and
+/// search.
+fn solve_puzzle(puzzle: Result<Puzzle, SolveError>, options: &SolveOptions, db: Option<&Mutex<DeadlockDb>>) -> Report {
+    let started = Instant::now();
+    let puzzle = match puzzle {
+        Ok(puzzle) => puzzle,
+        Err(err) => return Report { result: Err(err), stats: None, elapsed: started.elapsed() },
+    };
+    let mut solver = Solver::new(&puzzle);
+    if let Some(db) = db {
+        solver.learn(db.lock().deadlocks(&puzzle));
+    }
+    let result = solver.solve(options);
+    if let Some(db) = db {
+        db.lock().record(&puzzle, solver.learned());
+    }
+    Report {
+        result,
+        stats: Some(solver.stats().clone()),
+        elapsed: started.elapsed(),
     }
+}
 
-    #[inline(always)]
-    fn probe(&self, hash: u64) -> Option<i32> {
-        let idx = (hash as usize) % self.size;
-        let entry = unsafe { self.entries.get_unchecked(idx) };
-        if entry.0 == hash && entry.2 == self.age {
-            Some(entry.1)
-        } else {
-            None
-        }
+/// Reads a puzzle file, or standard input when the path is `-`.
+fn read_input(path: &str) -> Result<String, SolveError> {
+    let io_error = |e: std::io::Error| SolveError::Io { path: path.to_string(), message: e.to_string() };
+    if path == STDIN_PATH {
+        let mut text = String::new();
+        io::stdin().read_to_string(&mut text).map_err(io_error)?;
+        return Ok(text);
     }
+    fs::read_to_string(path).map_err(io_error)
+}
 
-    #[inline(always)]
-    fn store(&mut self, hash: u64, heuristic: i32) {
-        let idx = (hash as usize) % self.size;
-        unsafe {
-            *self.entries.get_unchecked_mut(idx) = (hash, heuristic, self.age);
+/// Parses a plain map, or one level of a collection: the one `level`
+/// selects, or else the first, if the text is not a single valid map.
+fn parse_puzzle(text: &str, level: Option<&str>) -> Result<Puzzle, SolveError> {
+    if let Some(selector) = level {
+        return Collection::parse(text).select(selector)?.1.puzzle();
+    }
+    match Puzzle::parse(text) {
+        // Bare maps separated by blank lines read as one map with several
+        // players, so an invalid map may still be a collection.
+        Err(err @ (SolveError::Parse { .. } | SolveError::InvalidPuzzle(_))) => {
+            // A broken plain map can split into bogus levels; keep its
+            // original error unless the first level really is a puzzle.
+            let first = Collection::parse(text).levels.first().map(Level::puzzle);
+            first.filter(Result::is_ok).unwrap_or(Err(err))
         }
+        other => other,
     }
+}
 
-    fn next_age(&mut self) {
-        self.age = self.age.wrapping_add(1);
-    }
+fn load_puzzle(path: &str, level: Option<&str>) -> Result<Puzzle, SolveError> {
+    parse_puzzle(&read_input(path)?, level)
 }
 
-struct SokobanSolver {
-    width: i16,
-    height: i16,
-    map: Vec<u8>,
-    goals: SmallVec<[Point; 20]>,
-    goal_grid: Vec<u64>,
-    dead_squares: Vec<u64>,
-    room_ids: Vec<u8>,
-    goal_counts_by_room: SmallVec<[i32; 8]>,
-    zobrist_table: Vec<[u64; 2]>,
-    tt: TranspositionTable,
+/// Reads the `--deadlock-db` file, if one was given.
+fn load_deadlock_db(args: &Args) -> Result<Option<Mutex<DeadlockDb>>, SolveError> {
+    args.deadlock_db.as_ref().map(|path| DeadlockDb::load(path).map(Mutex::new)).transpose()
 }
 
-impl SokobanSolver {
-    fn new(puzzle: &str) -> Self {
-        let lines: Vec<&str> = puzzle.lines().collect();
-        let height = lines.len() as i16;
-        let width = lines.iter().map(|l| l.len()).max().unwrap_or(0) as i16;
-
-        let size = (width * height) as usize;
-        let mut map = vec![0u8; size];
-        let mut goals = SmallVec::new();
-
-        for (row, line) in lines.iter().enumerate() {
-            for (col, ch) in line.chars().enumerate() {
-                let idx = row * width as usize + col;
-                map[idx] = match ch {
-                    '#' => 1,
-                    '.' | '+' | '*' => {
-                        goals.push(Point::new(row as i16, col as i16));
-                        2
-                    }
-                    _ => 0,
-                };
-            }
+fn run(args: &Args) -> Report {
+    let db = match load_deadlock_db(args) {
+        Ok(db) => db,
+        Err(err) => return Report { result: Err(err), stats: None, elapsed: Duration::ZERO },
+    };
+    let puzzle = load_puzzle(&args.puzzle_path, args.level.as_deref());
+    let mut report = solve_puzzle(puzzle, &args.options, db.as_ref());
+    if let (Some(path), Some(db)) = (&args.deadlock_db, db) {
+        // The solve itself stands; a database that cannot be written only
+        // costs the next run its head start.
+        if let Err(err) = db.into_inner().save(path) {
+            eprintln!("error: {}", err);
         }
-
-        let mut solver = SokobanSolver {
-            width,
-            height,
-            map,
-            goals,
-            goal_grid: vec![0u64; (size + 63) / 64],
-            dead_squares: vec![0u64; (size + 63) / 64],
-            room_ids: vec![255u8; size],
-            goal_counts_by_room: SmallVec::new(),
-            zobrist_table: vec![[0u64; 2]; size],
-            tt: TranspositionTable::new(1 << 20),
-        };
-
-        for goal in &solver.goals {
-            let idx = (goal.row * width + goal.col) as usize;
-            solver.goal_grid[idx / 64] |= 1u64 << (idx % 64);
+    }
+    if args.lowercase {
+        if let Ok(solution) = &mut report.result {
+            solution.moves.make_ascii_lowercase();
         }
-
-        solver.initialize_zobrist();
-        solver.precompute_static_deadlocks();
-        solver.precompute_rooms();
-        solver
     }
+    report
+}
 
-    fn initialize_zobrist(&mut self) {
-        use std::collections::hash_map::RandomState;
-        use std::hash::{BuildHasher, Hash, Hasher};
-
-        let random_state = RandomState::new();
-        let size = (self.width * self.height) as usize;
+fn options_json(options: &SolveOptions) -> Value {
+    json!({
+        "objective": options.objective.name(),
+        "heuristic": options.heuristic.name(),
+        "tt_size": options.tt_size,
+        "closed_set": options.closed_set.name(),
+        "time_limit": options.time_limit.map(|limit| limit.as_secs_f64()),
+        "max_nodes": options.max_nodes,
+        "max_memory": options.max_memory,
+        "patterns": options.patterns.name(),
+        "seed": options.seed,
+        "threads": options.threads,
+    })
+}
 
-        for i in 0..size {
-            let mut hasher1 = random_state.build_hasher();
-            (i, 0u8).hash(&mut hasher1);
-            self.zobrist_table[i][0] = hasher1.finish();
+fn stats_json(stats: &SolveStats) -> Value {
+    json!({
+        "nodes_expanded": stats.nodes_expanded,
+        "nodes_generated": stats.nodes_generated,
+        "closed_set_size": stats.closed_set_size,
+        "peak_open_set": stats.peak_open_set,
+        "start_heuristic": stats.start_heuristic,
+        "tt_hits": stats.tt_hits,
+        "tt_misses": stats.tt_misses,
+        "hash_collisions": stats.hash_collisions,
+        "freeze_deadlocks": stats.freeze_deadlocks,
+        "pattern_deadlocks": stats.pattern_deadlocks,
+        "matching_deadlocks": stats.matching_deadlocks,
+        "corral_deadlocks": stats.corral_deadlocks,
+        "learned_deadlocks": stats.learned_deadlocks,
+    })
+}
 
-            let mut hasher2 = random_state.build_hasher();
-            (i, 1u8).hash(&mut hasher2);
-            self.zobrist_table[i][1] = hasher2.finish();
+/// Outcome, solution and statistics of one puzzle.
+fn report_json(puzzle: &str, report: &Report) -> Value {
+    let (termination, solution, error, best) = match &report.result {
+        Ok(solution) => ("solved", json!(solution.moves), Value::Null, Value::Null),
+        Err(err) => {
+            let best = err.partial().map_or(Value::Null, |partial| {
+                json!({
+                    "moves": partial.moves,
+                    "pushes": partial.pushes,
+                    "boxes_on_goals": partial.boxes_on_goals,
+                })
+            });
+            (err.name(), Value::Null, json!(err.to_string()), best)
         }
-    }
-
-    fn precompute_static_deadlocks(&mut self) {
-        let size = (self.width * self.height) as usize;
-        let mut live_squares = vec![false; size];
-        let mut queue = std::collections::VecDeque::with_capacity(self.goals.len() * 4);
+    };
+    let solved = report.result.as_ref().ok();
+    json!({
+        "puzzle": puzzle,
+        "termination": termination,
+        "error": error,
+        "solution": solution,
+        "moves": solved.map(|s| s.moves.len()),
+        "pushes": solved.map(|s| s.pushes),
+        "best_state": best,
+        "elapsed_ms": report.elapsed.as_secs_f64() * 1000.0,
+        "stats": report.stats.as_ref().map_or(Value::Null, stats_json),
+    })
+}
 
-        for &goal in &self.goals {
-            let idx = (goal.row * self.width + goal.col) as usize;
-            live_squares[idx] = true;
-            queue.push_back(goal);
+/// `verify [--level <n|title>] <puzzle_file> <solution>`; returns the exit code.
+fn run_verify(args: &[String]) -> i32 {
+    let (level, args) = match args {
+        [flag, level, rest @ ..] if flag == "--level" => (Some(level.as_str()), rest),
+        _ => (None, args),
+    };
+    let [puzzle_path, solution] = args else {
+        eprintln!("error: verify needs a puzzle file and a solution\n\n{}", USAGE);
+        return EXIT_USAGE;
+    };
+    let puzzle = match load_puzzle(puzzle_path, level) {
+        Ok(puzzle) => puzzle,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            return exit_code(&err);
         }
-
-        while let Some(pull_target) = queue.pop_front() {
-            for &(drow, dcol) in &DIR_OFFSETS {
-                let pull_origin_row = pull_target.row + drow as i16;
-                let pull_origin_col = pull_target.col + dcol as i16;
-                let player_row = pull_origin_row + drow as i16;
-                let player_col = pull_origin_col + dcol as i16;
-
-                if self.is_valid(pull_origin_row, pull_origin_col)
-                    && self.is_valid(player_row, player_col)
-                {
-                    let po_idx = (pull_origin_row * self.width + pull_origin_col) as usize;
-                    let p_idx = (player_row * self.width + player_col) as usize;
-
-                    if self.map[po_idx] != 1 && self.map[p_idx] != 1 && !live_squares[po_idx] {
-                        live_squares[po_idx] = true;
-                        queue.push_back(Point::new(pull_origin_row, pull_origin_col));
-                    }
-                }
+    };
+    // Anything that is not a plain move string names a solution file.
+    let moves = if solution.chars().all(|c| "udlrUDLR".contains(c)) {
+        solution.clone()
+    } else {
+        match fs::read_to_string(solution) {
+            Ok(text) => text,
+            Err(err) => {
+                eprintln!("error: cannot read {}: {}", solution, err);
+                return EXIT_IO;
             }
         }
-
-        for i in 0..size {
-            if self.map[i] != 1 && !live_squares[i] {
-                self.dead_squares[i / 64] |= 1u64 << (i % 64);
+    };
+
+    match verify(&puzzle, &moves) {
+        Ok(replay) => {
+            println!("moves: {}", replay.moves);
+            println!("pushes: {}", replay.pushes);
+            println!("boxes on goals: {}/{}", replay.boxes_on_goals, puzzle.boxes().len());
+            println!("solved: {}", if replay.solved { "yes" } else { "no" });
+            if replay.solved {
+                0
+            } else {
+                EXIT_REJECTED
             }
         }
+        Err(illegal) => {
+            println!("moves: {}", illegal.index);
+            println!("pushes: {}", illegal.pushes);
+            println!("solved: no");
+            eprintln!("error: illegal {}", illegal);
+            EXIT_REJECTED
+        }
     }
+}
 
-    fn precompute_rooms(&mut self) {
-        self.goal_counts_by_room.clear();
-        let mut current_room_id = 0u8;
-
-        for row in 0..self.height {
-            for col in 0..self.width {
-                let idx = (row * self.width + col) as usize;
-                if self.map[idx] != 1 && self.room_ids[idx] == 255 {
-                    let goal_count = self.flood_fill_room(Point::new(row, col), current_room_id);
-                    self.goal_counts_by_room.push(goal_count);
-                    current_room_id += 1;
+/// `export [--rle] [--level <n|title>] <puzzle_file>`; returns the exit code.
+fn run_export(args: &[String]) -> i32 {
+    let mut rle = false;
+    let mut level = None;
+    let mut path = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--rle" => rle = true,
+            "--level" => match iter.next() {
+                Some(value) => level = Some(value),
+                None => {
+                    eprintln!("error: --level needs a value\n\n{}", USAGE);
+                    return EXIT_USAGE;
                 }
+            },
+            _ if path.is_none() && !arg.starts_with("--") => path = Some(arg),
+            _ => {
+                eprintln!("error: unexpected argument '{}'\n\n{}", arg, USAGE);
+                return EXIT_USAGE;
             }
         }
     }
-
-    #[inline(always)]
-    fn is_solved_boxes(&self, boxes: &[Point]) -> bool {
-        for &b in boxes {
-            let idx = self.to_idx(b.row, b.col);
-            if (self.goal_grid[idx / 64] & (1u64 << (idx % 64))) == 0 {
-                return false;
-            }
+    let Some(path) = path else {
+        eprintln!("error: export needs a puzzle file\n\n{}", USAGE);
+        return EXIT_USAGE;
+    };
+
+    match load_puzzle(path, level.map(String::as_str)) {
+        Ok(puzzle) if rle => println!("{}", puzzle.to_rle()),
+        Ok(puzzle) => print!("{}", puzzle.to_text()),
+        Err(err) => {
+            eprintln!("error: {}", err);
+            return exit_code(&err);
         }
-        true
-    }
-
-    #[inline(always)]
-    fn boxes_zobrist_key(&self, boxes: &[Point]) -> u64 {
-        let mut key: u64 = 0;
-        for &b in boxes {
-            let idx = self.to_idx(b.row, b.col);
-            key ^= self.zobrist_table[idx][1];
-        }   
-        key
     }
+    0
+}
 
-    #[inline(always)]
-    fn find_goal_index(&self, row: i16, col: i16) -> Option<usize> {
-        self.goals.iter().position(|g| g.row == row && g.col == col)
-    }
-
-    fn flood_fill_room(&mut self, start: Point, room_id: u8) -> i32 {
-        let mut goal_count = 0;
-        let mut queue = std::collections::VecDeque::with_capacity(100);
-        queue.push_back(start);
-        let start_idx = (start.row * self.width + start.col) as usize;
-        self.room_ids[start_idx] = room_id;
-
-        while let Some(current) = queue.pop_front() {
-            let idx = (current.row * self.width + current.col) as usize;
-            if (self.goal_grid[idx / 64] & (1u64 << (idx % 64))) != 0 {
-                goal_count += 1;
-            }
-
-            for &(drow, dcol) in &DIR_OFFSETS {
-                let new_row = current.row + drow as i16;
-                let new_col = current.col + dcol as i16;
-
-                if self.is_valid(new_row, new_col) {
-                    let new_idx = (new_row * self.width + new_col) as usize;
-                    if self.map[new_idx] != 1 && self.room_ids[new_idx] == 255 {
-                        self.room_ids[new_idx] = room_id;
-                        queue.push_back(Point::new(new_row, new_col));
-                    }
+/// `deadlocks [--clear] [--level <n|title>] <db_file> [<puzzle_file>]`;
+/// returns the exit code.
+fn run_deadlocks(args: &[String]) -> i32 {
+    let mut clear = false;
+    let mut level = None;
+    let mut paths = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--clear" => clear = true,
+            "--level" => match iter.next() {
+                Some(value) => level = Some(value),
+                None => {
+                    eprintln!("error: --level needs a value\n\n{}", USAGE);
+                    return EXIT_USAGE;
                 }
+            },
+            _ if paths.len() < 2 && !arg.starts_with("--") => paths.push(arg),
+            _ => {
+                eprintln!("error: unexpected argument '{}'\n\n{}", arg, USAGE);
+                return EXIT_USAGE;
             }
         }
-
-        goal_count
-    }
-
-    #[inline(always)]
-    fn is_valid(&self, row: i16, col: i16) -> bool {
-        row >= 0 && row < self.height && col >= 0 && col < self.width
     }
-
-    #[inline(always)]
-    fn to_idx(&self, row: i16, col: i16) -> usize {
-        (row * self.width + col) as usize
-    }
-
-    #[inline(always)]
-    fn calculate_zobrist_hash_incremental(
-        &self,
-        old_hash: u64,
-        old_player: Point,
-        new_player: Point,
-        old_box: Option<Point>,
-        new_box: Option<Point>,
-    ) -> u64 {
-        let mut hash = old_hash;
-
-        let old_p_idx = self.to_idx(old_player.row, old_player.col);
-        hash ^= self.zobrist_table[old_p_idx][0];
-
-        let new_p_idx = self.to_idx(new_player.row, new_player.col);
-        hash ^= self.zobrist_table[new_p_idx][0];
-
-        if let Some(old_b) = old_box {
-            let old_b_idx = self.to_idx(old_b.row, old_b.col);
-            hash ^= self.zobrist_table[old_b_idx][1];
-        }
-
-        if let Some(new_b) = new_box {
-            let new_b_idx = self.to_idx(new_b.row, new_b.col);
-            hash ^= self.zobrist_table[new_b_idx][1];
+    let Some(&db_path) = paths.first() else {
+        eprintln!("error: deadlocks needs a database file\n\n{}", USAGE);
+        return EXIT_USAGE;
+    };
+    let puzzle = paths.get(1).map(|path| load_puzzle(path, level.map(String::as_str))).transpose();
+    let (mut db, puzzle) = match (DeadlockDb::load(db_path), puzzle) {
+        (Ok(db), Ok(puzzle)) => (db, puzzle),
+        (Err(err), _) | (_, Err(err)) => {
+            eprintln!("error: {}", err);
+            return exit_code(&err);
         }
-
-        hash
-    }
-
-    #[inline(always)]
-    fn calculate_zobrist_hash(&self, player: &Point, boxes: &[Point]) -> u64 {
-        let p_idx = self.to_idx(player.row, player.col);
-        let mut hash = self.zobrist_table[p_idx][0];
-
-        for box_pos in boxes {
-            let b_idx = self.to_idx(box_pos.row, box_pos.col);
-            hash ^= self.zobrist_table[b_idx][1];
+    };
+
+    if clear {
+        let cleared = match &puzzle {
+            Some(puzzle) => db.remove(puzzle),
+            None => {
+                let all = db.len();
+                db.clear();
+                all
+            }
+        };
+        if let Err(err) = db.save(db_path) {
+            eprintln!("error: {}", err);
+            return exit_code(&err);
         }
-
-        hash
+        println!("cleared: {}", cleared);
+        return 0;
     }
 
-
-    fn calculate_heuristic(&self, boxes: &[Point]) -> i32 {
-        let box_key = self.boxes_zobrist_key(boxes);
-        if let Some(cached) = self.tt.probe(box_key) {
-            return cached;
-        }
-
-        let mut total_dist = 0;
-        let mut used_goal_mask: u64 = 0; // bitmask instead of ArrayVec<bool,32>
-        let mut boxes_on_goals = 0;
-
-        for &box_pos in boxes {
-            let idx = self.to_idx(box_pos.row, box_pos.col);
-
-            if (self.goal_grid[idx / 64] & (1u64 << (idx % 64))) != 0 {
-                if let Some(goal_index) = self.find_goal_index(box_pos.row, box_pos.col) {
-                    used_goal_mask |= 1u64 << goal_index;
-                }
-                boxes_on_goals += 1;
-                continue;
-            }
-
-            if self.is_frozen_box_ultra_fast(boxes, box_pos.row, box_pos.col) {
-                total_dist += 30;
-            }
-
-            let mut min_dist = i32::MAX;
-            let mut best_idx: Option<usize> = None;
-
-            for (i, goal) in self.goals.iter().enumerate() {
-                if (used_goal_mask & (1u64 << i)) == 0 {
-                    let dist = (box_pos.row - goal.row).abs() as i32
-                        + (box_pos.col - goal.col).abs() as i32;
-                    if dist < min_dist {
-                        min_dist = dist;
-                        best_idx = Some(i);
-                    }
-                }
-            }
-
-            if let Some(i) = best_idx {
-                used_goal_mask |= 1u64 << i;
-                total_dist += min_dist;
+    match &puzzle {
+        Some(puzzle) => {
+            let deadlocks = db.deadlocks(puzzle);
+            for deadlock in deadlocks {
+                println!("{}", deadlock.to_text(puzzle));
             }
+            println!("level: {:016x}", puzzle.layout_hash());
+            println!("deadlocks: {}", deadlocks.len());
         }
-
-        if boxes_on_goals == boxes.len() {
-            return 0;
-        }
-
-        total_dist
-    }
-
-    #[inline(always)]
-    fn mark_goal_as_matched(&self, used_goals: &mut ArrayVec<bool, 32>, row: i16, col: i16) {
-        for (i, goal) in self.goals.iter().enumerate() {
-            if goal.row == row && goal.col == col {
-                used_goals[i] = true;
-                return;
+        None => {
+            for (key, count) in db.levels() {
+                println!("{:016x} {}", key, count);
             }
+            println!("levels: {}", db.levels().count());
+            println!("deadlocks: {}", db.len());
         }
     }
+    0
+}
 
-    #[inline(always)]
-    fn is_frozen_box_ultra_fast(&self, boxes: &[Point], row: i16, col: i16) -> bool {
-        let idx = self.to_idx(row, col);
-        
-        if (self.goal_grid[idx / 64] & (1u64 << (idx % 64))) != 0 {
-            return false;
-        }
-
-        let has_obstacle = |r: i16, c: i16| -> bool {
-            if !self.is_valid(r, c) {
-                return true;
-            }
-            let i = self.to_idx(r, c);
-            if self.map[i] == 1 {
-                return true;
-            }
-            boxes.iter().any(|b| b.row == r && b.col == c)
-        };
-
-        let v_blocked = has_obstacle(row - 1, col) || has_obstacle(row + 1, col);
-        let h_blocked = has_obstacle(row, col - 1) || has_obstacle(row, col + 1);
-
-        v_blocked && h_blocked
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("verify") => process::exit(run_verify(&args[1..])),
+        Some("export") => process::exit(run_export(&args[1..])),
+        Some("batch") => process::exit(batch::run(&args[1..])),
+        Some("serve") => process::exit(serve::run(&args[1..])),
+        Some("deadlocks") => process::exit(run_deadlocks(&args[1..])),
+        _ => {}
     }
 
-    #[inline]
-    fn is_room_deadlock(&self, boxes: &[Point]) -> bool {
-        let mut box_counts: SmallVec<[i32; 8]> = SmallVec::new();
-        box_counts.resize(self.goal_counts_by_room.len(), 0);
-
-        for box_pos in boxes {
-            let idx = self.to_idx(box_pos.row, box_pos.col);
-            let room_id = self.room_ids[idx];
-            if room_id != 255 {
-                box_counts[room_id as usize] += 1;
-            }
+    let args = match parse_args(&args) {
+        Ok(args) => args,
+        Err(msg) => {
+            eprintln!("error: {}\n\n{}", msg, USAGE);
+            process::exit(EXIT_USAGE);
         }
-
-        for (room_id, &goal_count) in self.goal_counts_by_room.iter().enumerate() {
-            if box_counts[room_id] > goal_count {
-                return true;
-            }
+    };
+
+    let report = run(&args);
+    if args.format == Format::Json {
+        let mut json = report_json(&args.puzzle_path, &report);
+        json["level"] = json!(args.level);
+        json["config"] = options_json(&args.options);
+        json["config"]["deadlock_db"] = json!(args.deadlock_db);
+        println!("{}", json);
+        if let Err(err) = &report.result {
+            process::exit(exit_code(err));
         }
-
-        false
+        return;
     }
 
-    fn solve(&mut self, start_player: Point, start_boxes: BoxVec) -> String {
-        let start_hash = self.calculate_zobrist_hash(&start_player, &start_boxes);
-        let start_heuristic = self.calculate_heuristic(&start_boxes);
-
-        let start_state = State {
-            boxes: start_boxes,
-            player: start_player,
-            path: SmallVec::new(),
-            heuristic: start_heuristic,
-            hash: start_hash,
-        };
-
-        let mut open_set = BinaryHeap::with_capacity(10000);
-        let mut visited: FxHashSet<u64> = FxHashSet::with_capacity_and_hasher(200000, Default::default());
-
-        open_set.push(start_state);
-
-        while let Some(current) = open_set.pop() {
-            if self.is_solved_boxes(&current.boxes) {
-                return current.path.iter().map(|&dir| DIR_CHARS[dir as usize]).collect();
-            }
-
-            if !visited.insert(current.hash) {
-                continue;
-            }
-
-            for dir in 0..4 {
-                let (drow, dcol) = DIR_OFFSETS[dir];
-                let new_player_row = current.player.row + drow as i16;
-                let new_player_col = current.player.col + dcol as i16;
-
-                if !self.is_valid(new_player_row, new_player_col) {
-                    continue;
-                }
-
-                let new_p_idx = self.to_idx(new_player_row, new_player_col);
-                if self.map[new_p_idx] == 1 {
-                    continue;
-                }
-
-                let new_player = Point::new(new_player_row, new_player_col);
-                
-                let box_idx = current.boxes.iter().position(|b| b.row == new_player_row && b.col == new_player_col);
-
-                let mut new_boxes = current.boxes.clone();
-                let mut old_box = None;
-                let mut pushed_box = None;
-
-                if let Some(idx) = box_idx {
-                    let push_row = new_player_row + drow as i16;
-                    let push_col = new_player_col + dcol as i16;
-
-                    if !self.is_valid(push_row, push_col) {
-                        continue;
-                    }
-
-                    let push_idx = self.to_idx(push_row, push_col);
-                    if self.map[push_idx] == 1 {
-                        continue;
-                    }
-
-                    let push_pos = Point::new(push_row, push_col);
-                    if new_boxes.iter().any(|b| b.row == push_row && b.col == push_col) {
-                        continue;
-                    }
-
-                    if (self.dead_squares[push_idx / 64] & (1u64 << (push_idx % 64))) != 0 {
-                        continue;
-                    }
-
-                    old_box = Some(new_boxes[idx]);
-                    new_boxes[idx] = push_pos;
-                    pushed_box = Some(push_pos);
-
-                    if self.is_room_deadlock(&new_boxes) {
-                        continue;
-                    }
-                }
-
-                let new_hash = self.calculate_zobrist_hash_incremental(
-                    current.hash,
-                    current.player,
-                    new_player,
-                    old_box,
-                    pushed_box,
+    match report.result {
+        Ok(solution) => println!("{}", solution.moves),
+        Err(err) => {
+            eprintln!("error: {}", err);
+            if let Some(partial) = err.partial() {
+                eprintln!(
+                    "best state: {} boxes on goals after {} pushes ({} states expanded)",
+                    partial.boxes_on_goals, partial.pushes, partial.stats.nodes_expanded
                 );
-
-                if !visited.contains(&new_hash) {
-                    let new_heuristic = self.calculate_heuristic(&new_boxes);
-
-                    let mut new_path = current.path.clone();
-                    new_path.push(dir as u8);
-
-                    let next_state = State {
-                        boxes: new_boxes,
-                        player: new_player,
-                        path: new_path,
-                        heuristic: new_heuristic,
-                        hash: new_hash,
-                    };
-
-                    open_set.push(next_state);
-                }
-            }
-        }
-
-        String::new()
-    }
-}
-
-fn parse_puzzle(puzzle: &str) -> (Point, BoxVec, SokobanSolver) {
-    let lines: Vec<&str> = puzzle.lines().collect();
-    let mut player = Point::new(0, 0);
-    let mut boxes = BoxVec::new();
-
-    for (row, line) in lines.iter().enumerate() {
-        for (col, ch) in line.chars().enumerate() {
-            match ch {
-                '@' | '+' => player = Point::new(row as i16, col as i16),
-                '$' | '*' => boxes.push(Point::new(row as i16, col as i16)),
-                _ => {}
             }
+            process::exit(exit_code(&err));
         }
     }
-
-    let solver = SokobanSolver::new(puzzle);
-    (player, boxes, solver)
-}
-
-fn main() {
-    let args: Vec<String> = env::args().collect();
-    if args.len() < 2 {
-        eprintln!("Usage: rust_solver <puzzle_file>");
-        std::process::exit(1);
-    }
-
-    let puzzle_path = &args[1];
-    let puzzle = fs::read_to_string(puzzle_path).expect("Failed to read puzzle file");
-
-    let (player, boxes, mut solver) = parse_puzzle(&puzzle);
-    let solution = solver.solve(player, boxes);
-
-    println!("{}", solution);
 }
@@ -0,0 +1,87 @@
+use std::fmt;
+
+/// One of the four player directions, in `DIR_OFFSETS` order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl Direction {
+    pub const ALL: [Direction; 4] = [Direction::Up, Direction::Down, Direction::Left, Direction::Right];
+
+    #[inline(always)]
+    pub(crate) fn from_index(index: u8) -> Direction {
+        Direction::ALL[index as usize]
+    }
+
+    /// Row and column change of one step.
+    pub fn offset(self) -> (i16, i16) {
+        match self {
+            Direction::Up => (-1, 0),
+            Direction::Down => (1, 0),
+            Direction::Left => (0, -1),
+            Direction::Right => (0, 1),
+        }
+    }
+}
+
+/// One player step and whether it pushed a box.
+///
+/// Written in LURD notation: `u`, `d`, `l`, `r` for walking and the
+/// uppercase letter for a push.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Move {
+    pub direction: Direction,
+    pub push: bool,
+}
+
+impl Move {
+    pub fn walk(direction: Direction) -> Move {
+        Move { direction, push: false }
+    }
+
+    pub fn push(direction: Direction) -> Move {
+        Move { direction, push: true }
+    }
+
+    pub fn to_char(self) -> char {
+        let c = match self.direction {
+            Direction::Up => 'u',
+            Direction::Down => 'd',
+            Direction::Left => 'l',
+            Direction::Right => 'r',
+        };
+        if self.push {
+            c.to_ascii_uppercase()
+        } else {
+            c
+        }
+    }
+
+    /// Reads one LURD character. Case only records whether the step was
+    /// written as a push; whether it really pushes depends on the board.
+    pub fn from_char(c: char) -> Option<Move> {
+        let direction = match c.to_ascii_lowercase() {
+            'u' => Direction::Up,
+            'd' => Direction::Down,
+            'l' => Direction::Left,
+            'r' => Direction::Right,
+            _ => return None,
+        };
+        Some(Move { direction, push: c.is_ascii_uppercase() })
+    }
+}
+
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_char())
+    }
+}
+
+/// LURD string for a sequence of moves.
+pub fn to_lurd(moves: &[Move]) -> String {
+    moves.iter().map(|m| m.to_char()).collect()
+}
@@ -0,0 +1,187 @@
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, OnceLock};
+
+use crate::error::SolveError;
+
+/// 3x3 rotations: cell `i` of a rotated pattern is cell `ROTATION_PATTERNS[r][i]`
+/// of the original, cells numbered row by row.
+const ROTATION_PATTERNS: [[usize; 9]; 4] = [
+    [0, 1, 2, 3, 4, 5, 6, 7, 8],
+    [2, 5, 8, 1, 4, 7, 0, 3, 6],
+    [8, 7, 6, 5, 4, 3, 2, 1, 0],
+    [6, 3, 0, 7, 4, 1, 8, 5, 2],
+];
+
+/// Left-right mirror image, in the same numbering.
+const MIRROR_PATTERN: [usize; 9] = [2, 1, 0, 5, 4, 3, 8, 7, 6];
+
+/// The library used unless another is loaded.
+const BUILTIN_PATTERNS: &str = include_str!("../patterns.txt");
+
+// What a square holds, as bit flags; one of the first four plus `GOAL`.
+pub(crate) const WALL: u8 = 1;
+pub(crate) const BOX: u8 = 2;
+pub(crate) const FLOOR: u8 = 4;
+/// Floor without a box, on a dead square.
+pub(crate) const DEAD: u8 = 8;
+pub(crate) const GOAL: u8 = 16;
+const ANY: u8 = WALL | BOX | FLOOR | DEAD;
+
+/// Squares within two steps of a pushed box, row by row, as flags; the box
+/// is at the centre, index 12. Squares off the map are walls.
+pub(crate) type Area = [u8; 25];
+
+/// The squares a pattern cell accepts, as a mask of square flags.
+fn cell_from_char(c: char) -> Option<u8> {
+    match c {
+        '#' => Some(WALL),
+        '$' => Some(BOX),
+        'x' => Some(WALL | BOX),
+        '_' => Some(FLOOR | DEAD),
+        'd' => Some(DEAD),
+        '?' => Some(ANY),
+        _ => None,
+    }
+}
+
+/// One rotation or mirror image of a pattern placed with one of its box
+/// cells on the pushed box: the other cells that constrain anything, as
+/// `(area index, accepted flags)`, most selective first.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Placement {
+    cells: Vec<(usize, u8)>,
+}
+
+/// A library of 3x3 deadlock patterns, each stored in all of its distinct
+/// rotations and mirror images.
+///
+/// A pattern matches when the pushed box stands on one of its `$` or `x`
+/// cells and every cell accepts its square, and is a deadlock there unless
+/// every box it covers stands on a goal. The text format is three rows of
+/// three cells per pattern (`#` wall, `$` box, `x` wall or box, `_` floor
+/// without a box, `d` the same on a dead square, `?` anything), patterns
+/// separated by blank lines; lines starting with `;` are comments. See
+/// `patterns.txt` for the built-in library.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Patterns {
+    name: String,
+    count: usize,
+    placements: Vec<Placement>,
+}
+
+impl Patterns {
+    pub fn parse(name: &str, text: &str) -> Result<Patterns, SolveError> {
+        let mut patterns = Patterns { name: name.to_string(), count: 0, placements: Vec::new() };
+        let mut rows: Vec<(usize, &str)> = Vec::new();
+        let lines = text.lines().enumerate().map(|(i, line)| (i + 1, line.trim_end()));
+        for (number, line) in lines.chain(std::iter::once((0, ""))) {
+            if !line.is_empty() && !line.starts_with(';') {
+                rows.push((number, line));
+                continue;
+            }
+            if rows.is_empty() {
+                continue;
+            }
+            if rows.len() != 3 {
+                return Err(parse_error(rows[0].0, 1, format!("a pattern has 3 rows, found {}", rows.len())));
+            }
+            let mut cells = [ANY; 9];
+            for (row, &(number, line)) in rows.iter().enumerate() {
+                if line.chars().count() != 3 {
+                    return Err(parse_error(number, 1, "a pattern row has 3 cells".to_string()));
+                }
+                for (col, c) in line.chars().enumerate() {
+                    cells[row * 3 + col] = cell_from_char(c)
+                        .ok_or_else(|| parse_error(number, col + 1, format!("unexpected pattern cell '{}'", c)))?;
+                }
+            }
+            patterns.add(cells);
+            rows.clear();
+        }
+        Ok(patterns)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Patterns, SolveError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|e| SolveError::Io {
+            path: path.display().to_string(),
+            message: e.to_string(),
+        })?;
+        Patterns::parse(&path.display().to_string(), &text)
+    }
+
+    /// The library in `patterns.txt`, parsed once.
+    pub fn builtin() -> Arc<Patterns> {
+        static BUILTIN: OnceLock<Arc<Patterns>> = OnceLock::new();
+        BUILTIN
+            .get_or_init(|| Arc::new(Patterns::parse("builtin", BUILTIN_PATTERNS).expect("patterns.txt parses")))
+            .clone()
+    }
+
+    /// An empty library, which disables pattern deadlocks.
+    pub fn none() -> Patterns {
+        Patterns { name: "none".to_string(), count: 0, placements: Vec::new() }
+    }
+
+    /// `builtin`, `none`, or the path the library was loaded from.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Patterns in the library, not counting rotations and mirror images.
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    fn add(&mut self, cells: [u8; 9]) {
+        let mirrored = MIRROR_PATTERN.map(|i| cells[i]);
+        let mut variants: Vec<[u8; 9]> = Vec::new();
+        for base in [cells, mirrored] {
+            for rotation in &ROTATION_PATTERNS {
+                let variant = rotation.map(|i| base[i]);
+                if !variants.contains(&variant) {
+                    variants.push(variant);
+                }
+            }
+        }
+        for variant in variants {
+            for anchor in (0..9).filter(|&i| variant[i] & BOX != 0 && variant[i] != ANY) {
+                // Window placed so that the anchor cell lands on the centre.
+                let (top, left) = (2 - anchor / 3, 2 - anchor % 3);
+                let mut cells: Vec<(usize, u8)> = (0..9)
+                    .filter(|&i| i != anchor && variant[i] != ANY)
+                    .map(|i| ((top + i / 3) * 5 + left + i % 3, variant[i]))
+                    .collect();
+                cells.sort_by_key(|&(_, accepted)| accepted.count_ones());
+                cells.insert(0, (12, variant[anchor]));
+                self.placements.push(Placement { cells });
+            }
+        }
+        self.count += 1;
+    }
+
+    /// Whether some pattern matches around the pushed box at the centre of
+    /// `area` and covers a box off its goal.
+    pub(crate) fn deadlock_around(&self, area: &Area) -> bool {
+        self.placements.iter().any(|placement| {
+            let mut off_goal = false;
+            for &(idx, accepted) in &placement.cells {
+                let square = area[idx];
+                if square & accepted == 0 {
+                    return false;
+                }
+                off_goal |= square & (BOX | GOAL) == BOX;
+            }
+            off_goal
+        })
+    }
+}
+
+fn parse_error(line: usize, column: usize, message: String) -> SolveError {
+    SolveError::Parse { line, column, message }
+}
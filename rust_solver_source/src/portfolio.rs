@@ -0,0 +1,105 @@
+use parking_lot::Mutex;
+use rayon::prelude::*;
+use rayon::ThreadPoolBuilder;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::error::SolveError;
+use crate::heuristic::Heuristic;
+use crate::puzzle::{BoxVec, Point};
+use crate::solver::{Objective, Solution, SolveOptions, SokobanSolver, Strategy};
+
+/// The searches run for `options.threads`, in order of preference. The first
+/// is always the plain search the options describe, so a portfolio never
+/// does worse than `threads: 1` apart from sharing the machine.
+///
+/// Optimal objectives only vary the heuristic (both are admissible), so
+/// whichever search finishes first is still optimal. `Any` also tries
+/// weighted A* and a deeper tie-break.
+fn members(options: &SolveOptions) -> Vec<(SolveOptions, Strategy)> {
+    let other = match options.heuristic {
+        Heuristic::Matching => Heuristic::Greedy,
+        Heuristic::Greedy => Heuristic::Matching,
+    };
+    let configs: &[(Heuristic, Strategy)] = if options.objective == Objective::Any {
+        &[
+            (options.heuristic, Strategy::Plain),
+            (options.heuristic, Strategy::Weighted(2)),
+            (other, Strategy::Plain),
+            (options.heuristic, Strategy::Deepest),
+            (options.heuristic, Strategy::Weighted(4)),
+            (other, Strategy::Deepest),
+        ]
+    } else {
+        &[(options.heuristic, Strategy::Plain), (other, Strategy::Plain)]
+    };
+
+    let count = options.threads.clamp(1, configs.len());
+    configs[..count]
+        .iter()
+        .map(|&(heuristic, strategy)| {
+            let member = SolveOptions {
+                heuristic,
+                threads: 1,
+                // The memory budget covers the whole portfolio.
+                max_memory: options.max_memory.map(|max| max / count),
+                ..options.clone()
+            };
+            (member, strategy)
+        })
+        .collect()
+}
+
+/// A result that settles the puzzle for every member: a solution, or proof
+/// that none exists (every strategy explores the same state space).
+fn is_decisive(result: &Result<Solution, SolveError>) -> bool {
+    matches!(result, Ok(_) | Err(SolveError::Unsolvable))
+}
+
+/// Runs the portfolio on its own thread pool and returns the first decisive
+/// result. If no member settles the puzzle, the first member's error is
+/// returned, exactly as a single-threaded search would have reported it.
+/// `engine` is left holding the statistics of the search whose result is
+/// returned, and the deadlocks learned by all of them.
+pub(crate) fn solve(
+    engine: &mut SokobanSolver,
+    player: Point,
+    boxes: &BoxVec,
+    options: &SolveOptions,
+) -> Result<Solution, SolveError> {
+    let members = members(options);
+    let pool = match ThreadPoolBuilder::new().num_threads(members.len()).build() {
+        Ok(pool) if members.len() > 1 => pool,
+        _ => return engine.solve(player, boxes.clone(), &members[0].0, members[0].1, None),
+    };
+
+    let stop = AtomicBool::new(false);
+    let winner = Mutex::new(None);
+    let mut clones: Vec<SokobanSolver> = (1..members.len()).map(|_| engine.clone()).collect();
+    let engines: Vec<&mut SokobanSolver> = std::iter::once(&mut *engine).chain(clones.iter_mut()).collect();
+
+    let mut results: Vec<Result<Solution, SolveError>> = pool.install(|| {
+        engines
+            .into_par_iter()
+            .zip(members.par_iter())
+            .enumerate()
+            .map(|(index, (engine, (member, strategy)))| {
+                let result = engine.solve(player, boxes.clone(), member, *strategy, Some(&stop));
+                if is_decisive(&result) {
+                    winner.lock().get_or_insert(index);
+                    stop.store(true, Ordering::Relaxed);
+                }
+                result
+            })
+            .collect()
+    });
+
+    // Deadlocks proven by any member hold for every later search.
+    for clone in &clones {
+        engine.learn(clone.corrals.learned());
+    }
+    let index = winner.into_inner().unwrap_or(0);
+    if index > 0 {
+        engine.stats = clones[index - 1].stats.clone();
+    }
+    results.swap_remove(index)
+}
@@ -0,0 +1,282 @@
+use smallvec::SmallVec;
+use std::fs;
+use std::path::Path;
+
+use crate::error::SolveError;
+use crate::validate::validate;
+
+// Compact point using i16 for better cache performance
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[repr(C)]
+pub struct Point {
+    pub row: i16,
+    pub col: i16,
+}
+
+impl Point {
+    #[inline(always)]
+    pub const fn new(row: i16, col: i16) -> Self {
+        Point { row, col }
+    }
+}
+
+pub(crate) type BoxVec = SmallVec<[Point; 20]>;
+
+/// A Sokoban level: the static map plus the initial player and box positions.
+///
+/// The map is stored flat, row-major, with one byte per cell:
+/// `0` floor, `1` wall, `2` goal.
+#[derive(Clone, Debug)]
+pub struct Puzzle {
+    width: i16,
+    height: i16,
+    map: Vec<u8>,
+    goals: Vec<Point>,
+    boxes: Vec<Point>,
+    player: Point,
+}
+
+impl Puzzle {
+    /// Reads and parses a puzzle file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Puzzle, SolveError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|e| SolveError::Io {
+            path: path.display().to_string(),
+            message: e.to_string(),
+        })?;
+        Puzzle::parse(&text)
+    }
+
+    /// Parses a puzzle in standard Sokoban notation, one row per line.
+    ///
+    /// Rows may also be run-length encoded (`4#` is `####`) and separated by
+    /// `|` instead of newlines, so `4#|#@$.#|4#` is a whole puzzle; `-` and
+    /// `_` are floor, like space. Any other character is rejected with its
+    /// position. The parsed map is then validated (single player, matching
+    /// box and goal counts, enclosed by walls, ...) and all problems are
+    /// returned together as [`SolveError::InvalidPuzzle`].
+    pub fn parse(text: &str) -> Result<Puzzle, SolveError> {
+        let text = expand(text)?;
+        let lines: Vec<&str> = text.lines().collect();
+
+        if lines.iter().all(|l| l.trim().is_empty()) {
+            return Err(SolveError::Parse {
+                line: 1,
+                column: 1,
+                message: "empty puzzle".to_string(),
+            });
+        }
+
+        let height = lines.len();
+        let width = lines.iter().map(|l| l.len()).max().unwrap_or(0);
+        if width > i16::MAX as usize || height > i16::MAX as usize || width * height > i16::MAX as usize {
+            return Err(SolveError::Parse {
+                line: 1,
+                column: 1,
+                message: format!("puzzle too large ({}x{})", width, height),
+            });
+        }
+        let height = height as i16;
+        let width = width as i16;
+
+        let size = (width * height) as usize;
+        let mut map = vec![0u8; size];
+        let mut goals = Vec::new();
+        let mut boxes = Vec::new();
+        let mut players = Vec::new();
+
+        for (row, line) in lines.iter().enumerate() {
+            for (col, ch) in line.chars().enumerate() {
+                let idx = row * width as usize + col;
+                let point = Point::new(row as i16, col as i16);
+                map[idx] = match ch {
+                    '#' => 1,
+                    '.' | '+' | '*' => {
+                        goals.push(point);
+                        2
+                    }
+                    _ => 0,
+                };
+                match ch {
+                    '@' | '+' => players.push(point),
+                    '$' | '*' => boxes.push(point),
+                    _ => {}
+                }
+            }
+        }
+
+        let problems = validate(width, height, &map, &players, &boxes, &goals);
+        if !problems.is_empty() {
+            return Err(SolveError::InvalidPuzzle(problems));
+        }
+
+        Ok(Puzzle {
+            width,
+            height,
+            map,
+            goals,
+            boxes,
+            player: players[0],
+        })
+    }
+
+    #[inline]
+    pub fn width(&self) -> i16 {
+        self.width
+    }
+
+    #[inline]
+    pub fn height(&self) -> i16 {
+        self.height
+    }
+
+    #[inline]
+    pub fn player(&self) -> Point {
+        self.player
+    }
+
+    #[inline]
+    pub fn boxes(&self) -> &[Point] {
+        &self.boxes
+    }
+
+    #[inline]
+    pub fn goals(&self) -> &[Point] {
+        &self.goals
+    }
+
+    /// Returns true for walls and for anything outside the map rectangle.
+    #[inline]
+    pub fn is_wall(&self, p: Point) -> bool {
+        !self.in_bounds(p) || self.map[self.to_idx(p)] == 1
+    }
+
+    #[inline]
+    pub fn is_goal(&self, p: Point) -> bool {
+        self.in_bounds(p) && self.map[self.to_idx(p)] == 2
+    }
+
+    #[inline]
+    pub fn in_bounds(&self, p: Point) -> bool {
+        p.row >= 0 && p.row < self.height && p.col >= 0 && p.col < self.width
+    }
+
+    /// Stable hash of the walls and goals (FNV-1a), the same in every run
+    /// and build. Levels that differ only in where the boxes and player
+    /// start share it.
+    pub fn layout_hash(&self) -> u64 {
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        let size = self.width.to_le_bytes().into_iter().chain(self.height.to_le_bytes());
+        for byte in size.chain(self.map.iter().copied()) {
+            hash = (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3);
+        }
+        hash
+    }
+
+    #[inline(always)]
+    pub(crate) fn map(&self) -> &[u8] {
+        &self.map
+    }
+
+    #[inline(always)]
+    fn to_idx(&self, p: Point) -> usize {
+        (p.row * self.width + p.col) as usize
+    }
+
+    fn cell_char(&self, p: Point, boxes: &[Point], player: Point) -> char {
+        match (self.is_wall(p), self.is_goal(p), boxes.contains(&p), p == player) {
+            (true, ..) => '#',
+            (_, true, true, _) => '*',
+            (_, true, _, true) => '+',
+            (_, true, ..) => '.',
+            (_, _, true, _) => '$',
+            (.., true) => '@',
+            _ => ' ',
+        }
+    }
+
+    /// Rows in standard notation with `boxes` and `player` placed on this
+    /// puzzle's walls and goals, without trailing floor.
+    fn rows_with<'a>(&'a self, boxes: &'a [Point], player: Point) -> impl Iterator<Item = String> + 'a {
+        (0..self.height).map(move |row| {
+            let line: String = (0..self.width).map(|col| self.cell_char(Point::new(row, col), boxes, player)).collect();
+            line.trim_end().to_string()
+        })
+    }
+
+    /// Another position on this puzzle's walls and goals in standard
+    /// notation, one row per line.
+    pub(crate) fn text_with(&self, boxes: &[Point], player: Point) -> String {
+        self.rows_with(boxes, player).map(|row| row + "\n").collect()
+    }
+
+    /// The puzzle in standard notation, one row per line.
+    pub fn to_text(&self) -> String {
+        self.text_with(&self.boxes, self.player)
+    }
+
+    /// The puzzle as one run-length-encoded line: runs of two or more cells
+    /// are written as count and character, floor as `-`, rows joined by `|`.
+    pub fn to_rle(&self) -> String {
+        let rows: Vec<String> = self
+            .rows_with(&self.boxes, self.player)
+            .map(|row| {
+                let mut encoded = String::new();
+                let mut chars = row.chars().map(|c| if c == ' ' { '-' } else { c }).peekable();
+                while let Some(c) = chars.next() {
+                    let mut run = 1;
+                    while chars.next_if_eq(&c).is_some() {
+                        run += 1;
+                    }
+                    if run > 1 {
+                        encoded.push_str(&run.to_string());
+                    }
+                    encoded.push(c);
+                }
+                encoded
+            })
+            .collect();
+        rows.join("|")
+    }
+}
+
+/// Rewrites any accepted input as plain rows: expands run-length counts,
+/// turns `|` into line breaks and `-`/`_` into spaces, and rejects unknown
+/// characters. Error positions refer to the original text.
+fn expand(text: &str) -> Result<String, SolveError> {
+    let mut plain = String::with_capacity(text.len());
+    for (row, line) in text.lines().enumerate() {
+        let error = |col: usize, message: String| SolveError::Parse { line: row + 1, column: col + 1, message };
+        let mut count: Option<(usize, usize)> = None;
+
+        for (col, ch) in line.chars().enumerate() {
+            if let Some(digit) = ch.to_digit(10) {
+                let (start, n) = count.unwrap_or((col, 0));
+                let n = n * 10 + digit as usize;
+                if n > i16::MAX as usize {
+                    return Err(error(start, format!("run length {}... is too long", n)));
+                }
+                count = Some((start, n));
+                continue;
+            }
+            let ch = match ch {
+                '|' => '\n',
+                '-' | '_' => ' ',
+                '#' | '@' | '+' | '$' | '*' | '.' | ' ' => ch,
+                _ => return Err(error(col, format!("unexpected character {:?}", ch))),
+            };
+            let n = match count.take() {
+                Some((start, 0)) => return Err(error(start, "run length 0 drops the cell".to_string())),
+                Some((_, n)) => n,
+                None => 1,
+            };
+            plain.extend(std::iter::repeat_n(ch, n));
+        }
+
+        if let Some((start, _)) = count {
+            return Err(error(start, "run length not followed by a map character".to_string()));
+        }
+        plain.push('\n');
+    }
+    Ok(plain)
+}
@@ -0,0 +1,138 @@
+use crate::puzzle::Point;
+use crate::solver::DIR_OFFSETS;
+
+const NO_PARENT: u8 = u8::MAX;
+
+/// Player reachability for one box configuration.
+///
+/// Buffers are reused across calls; a generation stamp marks which cells
+/// were reached by the latest `compute`, so nothing is cleared between nodes.
+#[derive(Clone)]
+pub(crate) struct Reachability {
+    width: i16,
+    height: i16,
+    stamp: Vec<u32>,
+    dist: Vec<u16>,
+    parent_dir: Vec<u8>,
+    box_grid: Vec<bool>,
+    queue: Vec<Point>,
+    generation: u32,
+    top_left: Point,
+}
+
+impl Reachability {
+    pub(crate) fn new(width: i16, height: i16) -> Self {
+        let size = (width * height) as usize;
+        Reachability {
+            width,
+            height,
+            stamp: vec![0; size],
+            dist: vec![0; size],
+            parent_dir: vec![NO_PARENT; size],
+            box_grid: vec![false; size],
+            queue: Vec::with_capacity(size),
+            generation: 0,
+            top_left: Point::new(0, 0),
+        }
+    }
+
+    #[inline(always)]
+    fn to_idx(&self, p: Point) -> usize {
+        (p.row * self.width + p.col) as usize
+    }
+
+    /// Breadth-first walk from `start` through floor not occupied by `boxes`.
+    pub(crate) fn compute(&mut self, map: &[u8], boxes: &[Point], start: Point) {
+        self.generation = self.generation.wrapping_add(1);
+        if self.generation == 0 {
+            self.stamp.fill(0);
+            self.generation = 1;
+        }
+
+        for &b in boxes {
+            let idx = self.to_idx(b);
+            self.box_grid[idx] = true;
+        }
+
+        let start_idx = self.to_idx(start);
+        self.stamp[start_idx] = self.generation;
+        self.dist[start_idx] = 0;
+        self.parent_dir[start_idx] = NO_PARENT;
+        self.queue.clear();
+        self.queue.push(start);
+        let mut top_left_idx = start_idx;
+
+        let mut head = 0;
+        while head < self.queue.len() {
+            let current = self.queue[head];
+            head += 1;
+            let current_dist = self.dist[self.to_idx(current)];
+
+            for (dir, &(drow, dcol)) in DIR_OFFSETS.iter().enumerate() {
+                let next = Point::new(current.row + drow as i16, current.col + dcol as i16);
+                if next.row < 0 || next.row >= self.height || next.col < 0 || next.col >= self.width {
+                    continue;
+                }
+                let idx = self.to_idx(next);
+                if map[idx] == 1 || self.box_grid[idx] || self.stamp[idx] == self.generation {
+                    continue;
+                }
+                self.stamp[idx] = self.generation;
+                self.dist[idx] = current_dist + 1;
+                self.parent_dir[idx] = dir as u8;
+                top_left_idx = top_left_idx.min(idx);
+                self.queue.push(next);
+            }
+        }
+
+        for &b in boxes {
+            let idx = self.to_idx(b);
+            self.box_grid[idx] = false;
+        }
+
+        self.top_left = Point::new(
+            (top_left_idx / self.width as usize) as i16,
+            (top_left_idx % self.width as usize) as i16,
+        );
+    }
+
+    #[inline(always)]
+    pub(crate) fn is_reachable(&self, p: Point) -> bool {
+        p.row >= 0
+            && p.row < self.height
+            && p.col >= 0
+            && p.col < self.width
+            && self.stamp[self.to_idx(p)] == self.generation
+    }
+
+    /// Walking distance from the start of the last `compute`. Only
+    /// meaningful for reachable squares.
+    #[inline(always)]
+    pub(crate) fn distance(&self, p: Point) -> u16 {
+        self.dist[self.to_idx(p)]
+    }
+
+    /// Canonical player square for the region: its top-left cell.
+    #[inline(always)]
+    pub(crate) fn top_left(&self) -> Point {
+        self.top_left
+    }
+
+    /// Directions of a shortest walk from the start of the last `compute`
+    /// to `target`, which must be reachable.
+    pub(crate) fn walk_to(&self, target: Point) -> Vec<u8> {
+        let mut dirs = Vec::with_capacity(self.distance(target) as usize);
+        let mut current = target;
+        loop {
+            let dir = self.parent_dir[self.to_idx(current)];
+            if dir == NO_PARENT {
+                break;
+            }
+            dirs.push(dir);
+            let (drow, dcol) = DIR_OFFSETS[dir as usize];
+            current = Point::new(current.row - drow as i16, current.col - dcol as i16);
+        }
+        dirs.reverse();
+        dirs
+    }
+}
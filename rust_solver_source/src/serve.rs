@@ -0,0 +1,349 @@
+//! `rust_solver serve`: a long-lived solver answering JSON lines on stdin.
+//!
+//! Every request is one JSON object per line with a `type` and an optional
+//! `id`, which is copied into every response to it. Responses are one JSON
+//! object per line on stdout, named by their `event`:
+//!
+//! - `solve` (`puzzle`, optional `level`, `options`, `lowercase`,
+//!   `progress_ms`): queued and solved one at a time. While it runs,
+//!   `progress` events report the counters every `progress_ms` (default 500,
+//!   0 for none); it ends with a `result` event shaped like `--format json`.
+//! - `verify` (`puzzle`, optional `level`, `solution`): answered with `verify`.
+//! - `cancel` (optional `target`: the id of a solve; default the oldest
+//!   unfinished one): answered with `cancel`. The cancelled solve still ends
+//!   with its `result` event, terminated `cancelled`.
+//! - `stats`: answered with `stats`, the server's counters.
+//!
+//! Malformed requests get an `error` event. At the end of input the server
+//! finishes the queued solves and exits.
+
+use rust_solver::{
+    verify, ClosedSetMode, Heuristic, Objective, Progress, Puzzle, SolveError, SolveOptions, SolveStats, Solver,
+};
+use serde_json::{json, Value};
+use std::io::{self, BufRead, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+
+use crate::{load_patterns, options_json, parse_puzzle, report_json, stats_json, Report, EXIT_USAGE, USAGE};
+
+const DEFAULT_PROGRESS_INTERVAL: Duration = Duration::from_millis(500);
+const REQUEST_TYPES: [&str; 4] = ["solve", "verify", "cancel", "stats"];
+
+/// Writes responses, one line each, flushed at once so clients see them.
+#[derive(Clone)]
+struct Output(Arc<Mutex<io::Stdout>>);
+
+impl Output {
+    fn send(&self, id: &Value, event: &str, mut body: Value) {
+        body["id"] = id.clone();
+        body["event"] = json!(event);
+        let mut stdout = self.0.lock();
+        // A client that stopped reading cannot be told anything anyway.
+        let _ = writeln!(stdout, "{}", body).and_then(|_| stdout.flush());
+    }
+
+    fn error(&self, id: &Value, message: &str) {
+        self.send(id, "error", json!({ "error": message }));
+    }
+}
+
+/// Counters and unfinished solves, shared by the reader and the worker.
+#[derive(Default)]
+struct Server {
+    /// Queued and running solves, oldest (the running one) first.
+    unfinished: Vec<(Value, Arc<AtomicBool>)>,
+    requests: u64,
+    solves: u64,
+    solved: u64,
+    /// Solves that reused the previous solve's precomputation.
+    reused: u64,
+    last_stats: Option<SolveStats>,
+}
+
+struct Job {
+    id: Value,
+    puzzle: Result<Puzzle, SolveError>,
+    options: SolveOptions,
+    lowercase: bool,
+    progress_interval: Option<Duration>,
+}
+
+fn named<T>(key: &str, value: &Value, from_name: fn(&str) -> Option<T>, names: &[&str]) -> Result<T, String> {
+    value.as_str().and_then(from_name).ok_or_else(|| {
+        format!("unknown {} {} (expected one of: {})", key.replace('_', "-"), value, names.join(", "))
+    })
+}
+
+/// Inverse of `options_json`: the same keys, units and names.
+fn parse_options(value: &Value) -> Result<SolveOptions, String> {
+    let mut options = SolveOptions::default();
+    let fields = match value {
+        Value::Null => return Ok(options),
+        Value::Object(fields) => fields,
+        _ => return Err("\"options\" must be an object".to_string()),
+    };
+    for (key, value) in fields {
+        let invalid = || format!("invalid {} {}", key, value);
+        let count = || value.as_u64().ok_or_else(invalid);
+        match key.as_str() {
+            "objective" => options.objective = named(key, value, Objective::from_name, &Objective::NAMES)?,
+            "heuristic" => options.heuristic = named(key, value, Heuristic::from_name, &Heuristic::NAMES)?,
+            "closed_set" => options.closed_set = named(key, value, ClosedSetMode::from_name, &ClosedSetMode::NAMES)?,
+            "tt_size" => options.tt_size = count()? as usize,
+            "time_limit" if value.is_null() => options.time_limit = None,
+            "time_limit" => {
+                let seconds = value.as_f64().ok_or_else(invalid)?;
+                options.time_limit = Some(Duration::try_from_secs_f64(seconds).map_err(|_| invalid())?);
+            }
+            "max_nodes" if value.is_null() => options.max_nodes = None,
+            "max_nodes" => options.max_nodes = Some(count()?),
+            "max_memory" if value.is_null() => options.max_memory = None,
+            "max_memory" => options.max_memory = Some(count()? as usize),
+            "patterns" => {
+                let value = value.as_str().ok_or_else(invalid)?;
+                options.patterns = load_patterns(value).map_err(|err| err.to_string())?;
+            }
+            "seed" => options.seed = count()?,
+            "threads" => options.threads = Some(count()? as usize).filter(|&n| n > 0).ok_or_else(invalid)?,
+            _ => return Err(format!("unknown option '{}'", key)),
+        }
+    }
+    Ok(options)
+}
+
+/// The request's puzzle, parsed like a puzzle file. A missing `puzzle` is
+/// a protocol error; a puzzle that does not parse is the solve's result.
+fn request_puzzle(request: &Value) -> Result<Result<Puzzle, SolveError>, String> {
+    let text = request["puzzle"].as_str().ok_or("request needs a \"puzzle\" string")?;
+    let level = match &request["level"] {
+        Value::Null => None,
+        Value::String(level) => Some(level.clone()),
+        Value::Number(level) => Some(level.to_string()),
+        other => return Err(format!("invalid level {}", other)),
+    };
+    Ok(parse_puzzle(text, level.as_deref()))
+}
+
+fn solve_job(id: &Value, request: &Value) -> Result<Job, String> {
+    let progress_interval = match &request["progress_ms"] {
+        Value::Null => Some(DEFAULT_PROGRESS_INTERVAL),
+        value => match value.as_u64() {
+            Some(0) => None,
+            Some(ms) => Some(Duration::from_millis(ms)),
+            None => return Err(format!("invalid progress_ms {}", value)),
+        },
+    };
+    Ok(Job {
+        id: id.clone(),
+        puzzle: request_puzzle(request)?,
+        options: parse_options(&request["options"])?,
+        lowercase: request["lowercase"].as_bool().unwrap_or(false),
+        progress_interval,
+    })
+}
+
+fn verify_response(request: &Value) -> Result<Value, String> {
+    let puzzle = request_puzzle(request)?.map_err(|err| err.to_string())?;
+    let solution = request["solution"].as_str().ok_or("request needs a \"solution\" string")?;
+    let boxes = puzzle.boxes().len();
+    Ok(match verify(&puzzle, solution) {
+        Ok(replay) => json!({
+            "moves": replay.moves,
+            "pushes": replay.pushes,
+            "boxes_on_goals": replay.boxes_on_goals,
+            "boxes": boxes,
+            "solved": replay.solved,
+            "illegal": null,
+        }),
+        Err(illegal) => json!({
+            "moves": illegal.index,
+            "pushes": illegal.pushes,
+            "boxes": boxes,
+            "solved": false,
+            "illegal": {
+                "index": illegal.index,
+                "character": illegal.character.to_string(),
+                "message": illegal.to_string(),
+            },
+        }),
+    })
+}
+
+/// Sets the cancel flag of `target`, or of the oldest unfinished solve.
+fn cancel(server: &Mutex<Server>, target: &Value) -> usize {
+    let server = server.lock();
+    let matching: Vec<&Arc<AtomicBool>> = match target {
+        Value::Null => server.unfinished.iter().take(1).map(|(_, flag)| flag).collect(),
+        target => server.unfinished.iter().filter(|(id, _)| id == target).map(|(_, flag)| flag).collect(),
+    };
+    for flag in &matching {
+        flag.store(true, Ordering::Relaxed);
+    }
+    matching.len()
+}
+
+fn stats_response(server: &Mutex<Server>) -> Value {
+    let server = server.lock();
+    json!({
+        "requests": server.requests,
+        "solves": server.solves,
+        "solved": server.solved,
+        "reused": server.reused,
+        "running": server.unfinished.first().map(|(id, _)| id.clone()),
+        "queued": server.unfinished.len().saturating_sub(1),
+        "last": server.last_stats.as_ref().map_or(Value::Null, stats_json),
+    })
+}
+
+/// Solves `job` on `solver`, sending progress events from a second thread
+/// until the search returns.
+fn search(solver: &mut Solver, job: &Job, options: &SolveOptions, output: &Output) -> Report {
+    let started = Instant::now();
+    let progress = Arc::new(Progress::default());
+    let options = SolveOptions { progress: Some(progress.clone()), ..options.clone() };
+    let (done, finished) = mpsc::channel::<()>();
+
+    let result = thread::scope(|scope| {
+        if let Some(interval) = job.progress_interval {
+            scope.spawn(move || {
+                while let Err(RecvTimeoutError::Timeout) = finished.recv_timeout(interval) {
+                    let body = json!({
+                        "elapsed_ms": started.elapsed().as_secs_f64() * 1000.0,
+                        "nodes_expanded": progress.nodes_expanded(),
+                        "nodes_generated": progress.nodes_generated(),
+                        "best_heuristic": progress.best_heuristic(),
+                    });
+                    output.send(&job.id, "progress", body);
+                }
+            });
+        }
+        let result = solver.solve(&options);
+        drop(done);
+        result
+    });
+    Report { result, stats: Some(solver.stats().clone()), elapsed: started.elapsed() }
+}
+
+/// Runs queued solves in order. The last puzzle's `Solver` is kept, so
+/// solving the same puzzle again skips its precomputation.
+fn work(jobs: Receiver<Job>, server: Arc<Mutex<Server>>, output: Output) {
+    let mut cached: Option<(String, Solver)> = None;
+    for job in jobs {
+        let cancel = server.lock().unfinished.first().map(|(_, flag)| flag.clone()).unwrap_or_default();
+        let options = SolveOptions { cancel: Some(cancel.clone()), ..job.options.clone() };
+
+        let mut report = match &job.puzzle {
+            Err(err) => Report { result: Err(err.clone()), stats: None, elapsed: Duration::ZERO },
+            Ok(_) if cancel.load(Ordering::Relaxed) => {
+                Report { result: Err(SolveError::Cancelled), stats: None, elapsed: Duration::ZERO }
+            }
+            Ok(puzzle) => {
+                let key = puzzle.to_text();
+                let reused = cached.as_ref().is_some_and(|(text, _)| *text == key);
+                if reused {
+                    server.lock().reused += 1;
+                } else {
+                    cached = Some((key, Solver::new(puzzle)));
+                }
+                let solver = &mut cached.as_mut().expect("solver cached above").1;
+                search(solver, &job, &options, &output)
+            }
+        };
+        if job.lowercase {
+            if let Ok(solution) = &mut report.result {
+                solution.moves.make_ascii_lowercase();
+            }
+        }
+
+        {
+            let mut server = server.lock();
+            server.unfinished.remove(0);
+            server.solves += 1;
+            server.solved += u64::from(report.result.is_ok());
+            if report.stats.is_some() {
+                server.last_stats = report.stats.clone();
+            }
+        }
+        let mut body = report_json("", &report);
+        if let Some(fields) = body.as_object_mut() {
+            fields.remove("puzzle");
+        }
+        body["config"] = options_json(&job.options);
+        output.send(&job.id, "result", body);
+    }
+}
+
+/// Answers one request line; solves are handed to the worker.
+fn handle(line: &str, server: &Mutex<Server>, jobs: &Sender<Job>, output: &Output) {
+    server.lock().requests += 1;
+    let request = match serde_json::from_str::<Value>(line) {
+        Ok(request @ Value::Object(_)) => request,
+        Ok(_) => return output.error(&Value::Null, "a request must be a JSON object"),
+        Err(err) => return output.error(&Value::Null, &format!("invalid JSON: {}", err)),
+    };
+    let id = request.get("id").cloned().unwrap_or(Value::Null);
+
+    match request["type"].as_str() {
+        Some("solve") => match solve_job(&id, &request) {
+            Ok(job) => {
+                server.lock().unfinished.push((id, Arc::default()));
+                // The worker only stops once this sender is dropped.
+                let _ = jobs.send(job);
+            }
+            Err(msg) => output.error(&id, &msg),
+        },
+        Some("verify") => match verify_response(&request) {
+            Ok(body) => output.send(&id, "verify", body),
+            Err(msg) => output.error(&id, &msg),
+        },
+        Some("cancel") => {
+            let target = request.get("target").cloned().unwrap_or(Value::Null);
+            let cancelled = cancel(server, &target);
+            output.send(&id, "cancel", json!({ "target": target, "cancelled": cancelled }));
+        }
+        Some("stats") => output.send(&id, "stats", stats_response(server)),
+        Some(other) => output.error(
+            &id,
+            &format!("unknown request type '{}' (expected one of: {})", other, REQUEST_TYPES.join(", ")),
+        ),
+        None => output.error(&id, "a request needs a \"type\""),
+    }
+}
+
+/// `serve`: answers requests until the end of input; returns the exit code.
+pub(crate) fn run(args: &[String]) -> i32 {
+    if let Some(arg) = args.first() {
+        eprintln!("error: unexpected argument '{}'\n\n{}", arg, USAGE);
+        return EXIT_USAGE;
+    }
+    let output = Output(Arc::new(Mutex::new(io::stdout())));
+    let server = Arc::new(Mutex::new(Server::default()));
+    let (jobs, queue) = mpsc::channel();
+    let worker = thread::spawn({
+        let server = server.clone();
+        let output = output.clone();
+        move || work(queue, server, output)
+    });
+
+    for line in io::stdin().lock().lines() {
+        match line {
+            Ok(line) if line.trim().is_empty() => {}
+            Ok(line) => handle(&line, &server, &jobs, &output),
+            Err(err) => {
+                eprintln!("error: cannot read request: {}", err);
+                break;
+            }
+        }
+    }
+    drop(jobs);
+    // Queued solves still run; their results are the last lines written.
+    let _ = worker.join();
+    0
+}
+
@@ -0,0 +1,1208 @@
+use smallvec::SmallVec;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::mem::size_of;
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicU64, Ordering as AtomicOrdering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::closed::{ClosedSet, ClosedSetMode, StateKey};
+use crate::corral::Corrals;
+use crate::error::SolveError;
+use crate::heuristic::{has_perfect_matching, min_cost_matching, Heuristic, PushDistances, UNREACHABLE};
+use crate::learned::LearnedDeadlock;
+use crate::moves::{to_lurd, Direction, Move};
+use crate::pattern::{self, Area, Patterns};
+use crate::portfolio;
+use crate::puzzle::{BoxVec, Point, Puzzle};
+use crate::reach::Reachability;
+
+// Up, down, left, right; indexed like `Direction`.
+pub(crate) const DIR_OFFSETS: [(i8, i8); 4] = [(-1, 0), (1, 0), (0, -1), (0, 1)];
+
+// Heuristic value for box layouts that cannot be completed. Finite so that
+// priority arithmetic cannot overflow; such nodes sort after everything else.
+const DEAD_END: i32 = 1 << 28;
+
+// Expansions between samples of the clock, the cancel flags, memory use and
+// `SolveOptions::progress`.
+const SAMPLE_INTERVAL: u64 = 1024;
+
+// Goals are tracked in u64 bitmasks (see `calculate_heuristic`).
+pub(crate) const MAX_GOALS: usize = 64;
+
+/// What the search minimizes.
+///
+/// `Any` is greedy best-first on an inadmissible heuristic: fast, but the
+/// solution can be far from the shortest. The other objectives run A* with an
+/// admissible, consistent lower bound, so the first solution popped is optimal
+/// for that objective. The two-part objectives minimize the first count and
+/// break ties on the second.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Objective {
+    #[default]
+    Any,
+    Moves,
+    Pushes,
+    PushesThenMoves,
+    MovesThenPushes,
+}
+
+impl Objective {
+    pub const NAMES: [&'static str; 5] = ["any", "moves", "pushes", "pushes-then-moves", "moves-then-pushes"];
+
+    pub fn from_name(name: &str) -> Option<Objective> {
+        match name {
+            "any" => Some(Objective::Any),
+            "moves" => Some(Objective::Moves),
+            "pushes" => Some(Objective::Pushes),
+            "pushes-then-moves" => Some(Objective::PushesThenMoves),
+            "moves-then-pushes" => Some(Objective::MovesThenPushes),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Objective::Any => "any",
+            Objective::Moves => "moves",
+            Objective::Pushes => "pushes",
+            Objective::PushesThenMoves => "pushes-then-moves",
+            Objective::MovesThenPushes => "moves-then-pushes",
+        }
+    }
+
+    /// Whether the player's exact square matters, not just its region. Any
+    /// objective that counts moves must keep states apart by where the
+    /// player stands, since that changes the walking cost of the next push.
+    #[inline(always)]
+    fn counts_moves(self) -> bool {
+        !matches!(self, Objective::Any | Objective::Pushes)
+    }
+
+    /// Open-set key for a node, smallest first. `h` is the greedy heuristic
+    /// for `Any` and the push lower bound otherwise; a push lower bound is
+    /// also a move lower bound since every push is a move.
+    #[inline(always)]
+    fn priority(self, moves: i32, pushes: i32, h: i32) -> (i32, i32) {
+        match self {
+            Objective::Any => (h, moves),
+            Objective::Moves => (moves + h, 0),
+            Objective::Pushes => (pushes + h, 0),
+            Objective::PushesThenMoves => (pushes + h, moves + h),
+            Objective::MovesThenPushes => (moves + h, pushes + h),
+        }
+    }
+}
+
+/// How a search orders its open set. Only `Plain` honours the objective's
+/// optimality guarantee; the others are alternative greedy searches used by
+/// the parallel portfolio.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Strategy {
+    Plain,
+    /// Weighted A* on pushes: f = pushes + w * h, ties to the lower h.
+    Weighted(i32),
+    /// Greedy on h, ties to the node with more pushes (depth-first flavour).
+    Deepest,
+}
+
+impl Strategy {
+    #[inline(always)]
+    fn priority(self, objective: Objective, moves: i32, pushes: i32, h: i32) -> (i32, i32) {
+        match self {
+            Strategy::Plain => objective.priority(moves, pushes, h),
+            Strategy::Weighted(w) => (pushes + w * h, h),
+            Strategy::Deepest => (h, -pushes),
+        }
+    }
+}
+
+// One push in the search tree. Nodes live in an arena and point at their
+// parent, so a path costs one node per push no matter how long it gets and
+// is only materialized when a solution is found.
+#[derive(Clone, Copy)]
+struct PushNode {
+    parent: u32,
+    box_pos: Point,
+    dir: u8,
+}
+
+const ROOT: u32 = u32::MAX;
+
+// A state is reached by a push; the player stands where the pushed box was.
+// Walks between pushes are not stored and are rebuilt in `reconstruct_moves`.
+struct State {
+    boxes: BoxVec,
+    player: Point,
+    node: u32,
+    pushes: u32,
+    moves: u32,
+    heuristic: i32,
+    priority: (i32, i32),
+    box_hash: u64,
+}
+
+impl Eq for State {}
+impl PartialEq for State {
+    #[inline(always)]
+    fn eq(&self, other: &Self) -> bool {
+        self.priority == other.priority && self.heuristic == other.heuristic
+    }
+}
+
+impl Ord for State {
+    // Ties on priority go to the lower heuristic, i.e. the node further along.
+    #[inline(always)]
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.priority.cmp(&self.priority)
+            .then_with(|| other.heuristic.cmp(&self.heuristic))
+    }
+}
+
+impl PartialOrd for State {
+    #[inline(always)]
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[derive(Clone)]
+struct TranspositionTable {
+    entries: Vec<(u64, i32, u8)>,
+    size: usize,
+    age: u8,
+}
+
+// Heuristic cache keyed by the box-only Zobrist hash. A size of 0 disables it.
+impl TranspositionTable {
+    fn new(size: usize) -> Self {
+        TranspositionTable {
+            entries: vec![(0, 0, 0); size],
+            size,
+            age: 0,
+        }
+    }
+
+    fn resize(&mut self, size: usize) {
+        if size != self.size {
+            *self = TranspositionTable::new(size);
+        }
+    }
+
+    #[inline(always)]
+    fn probe(&self, hash: u64) -> Option<i32> {
+        if self.size == 0 {
+            return None;
+        }
+        let idx = (hash as usize) % self.size;
+        let entry = unsafe { self.entries.get_unchecked(idx) };
+        if entry.0 == hash && entry.2 == self.age {
+            Some(entry.1)
+        } else {
+            None
+        }
+    }
+
+    #[inline(always)]
+    fn store(&mut self, hash: u64, heuristic: i32) {
+        if self.size == 0 {
+            return;
+        }
+        let idx = (hash as usize) % self.size;
+        unsafe {
+            *self.entries.get_unchecked_mut(idx) = (hash, heuristic, self.age);
+        }
+    }
+
+    // Invalidates every entry at once; called per solve because cached
+    // values depend on the heuristic and objective in use.
+    fn next_age(&mut self) {
+        self.age = self.age.wrapping_add(1);
+        if self.age == 0 {
+            self.entries.fill((0, 0, 0));
+            self.age = 1;
+        }
+    }
+}
+
+// SplitMix64: tiny, fast, and good enough for Zobrist keys. Seeded so that
+// hashes (and with them tie-breaking) are identical from run to run.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    #[inline(always)]
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct SokobanSolver {
+    width: i16,
+    height: i16,
+    map: Vec<u8>,
+    goals: SmallVec<[Point; 20]>,
+    goal_grid: Vec<u64>,
+    dead_squares: Vec<u64>,
+    room_ids: Vec<u8>,
+    goal_counts_by_room: SmallVec<[i32; 8]>,
+    zobrist_table: Vec<[u64; 2]>,
+    zobrist_seed: u64,
+    floor_index: Vec<u16>,
+    floor_words: usize,
+    tt: TranspositionTable,
+    pub(crate) stats: SolveStats,
+    reach: Reachability,
+    push_dist: PushDistances,
+    /// Goals a box on each square can still be pushed to, as a bitmask.
+    reachable_goals: Vec<u64>,
+    /// Empty-board pattern flags of every square (see `pattern::Area`).
+    squares: Vec<u8>,
+    pub(crate) corrals: Corrals,
+}
+
+impl SokobanSolver {
+    pub(crate) fn new(puzzle: &Puzzle) -> Self {
+        let width = puzzle.width();
+        let height = puzzle.height();
+        let size = (width * height) as usize;
+        let map = puzzle.map().to_vec();
+        let goals = puzzle.goals().iter().copied().collect();
+
+        let mut solver = SokobanSolver {
+            width,
+            height,
+            map,
+            goals,
+            goal_grid: vec![0u64; size.div_ceil(64)],
+            dead_squares: vec![0u64; size.div_ceil(64)],
+            room_ids: vec![255u8; size],
+            goal_counts_by_room: SmallVec::new(),
+            zobrist_table: vec![[0u64; 2]; size],
+            zobrist_seed: DEFAULT_SEED,
+            floor_index: Vec::new(),
+            floor_words: 0,
+            tt: TranspositionTable::new(0),
+            stats: SolveStats::default(),
+            reach: Reachability::new(width, height),
+            push_dist: PushDistances::new(width, height, puzzle.map(), puzzle.goals()),
+            reachable_goals: Vec::new(),
+            squares: Vec::new(),
+            corrals: Corrals::new(width, height),
+        };
+
+        for goal in &solver.goals {
+            let idx = (goal.row * width + goal.col) as usize;
+            solver.goal_grid[idx / 64] |= 1u64 << (idx % 64);
+        }
+
+        solver.initialize_zobrist(DEFAULT_SEED);
+        solver.index_floor();
+        solver.precompute_static_deadlocks();
+        solver.precompute_rooms();
+        solver.precompute_squares();
+        solver.precompute_reachable_goals();
+        solver
+    }
+
+    fn initialize_zobrist(&mut self, seed: u64) {
+        let mut rng = SplitMix64(seed);
+        for keys in self.zobrist_table.iter_mut() {
+            keys[0] = rng.next_u64();
+            keys[1] = rng.next_u64();
+        }
+        self.zobrist_seed = seed;
+    }
+
+    // Numbers the non-wall cells densely so state keys need one bit per
+    // square a box can actually occupy.
+    fn index_floor(&mut self) {
+        let mut next = 0u16;
+        self.floor_index = self
+            .map
+            .iter()
+            .map(|&cell| {
+                let idx = next;
+                if cell != 1 {
+                    next += 1;
+                }
+                idx
+            })
+            .collect();
+        self.floor_words = (next as usize).div_ceil(64);
+    }
+
+    fn precompute_static_deadlocks(&mut self) {
+        let size = (self.width * self.height) as usize;
+        let mut live_squares = vec![false; size];
+        let mut queue = std::collections::VecDeque::with_capacity(self.goals.len() * 4);
+
+        for &goal in &self.goals {
+            let idx = (goal.row * self.width + goal.col) as usize;
+            live_squares[idx] = true;
+            queue.push_back(goal);
+        }
+
+        while let Some(pull_target) = queue.pop_front() {
+            for &(drow, dcol) in &DIR_OFFSETS {
+                let pull_origin_row = pull_target.row + drow as i16;
+                let pull_origin_col = pull_target.col + dcol as i16;
+                let player_row = pull_origin_row + drow as i16;
+                let player_col = pull_origin_col + dcol as i16;
+
+                if self.is_valid(pull_origin_row, pull_origin_col)
+                    && self.is_valid(player_row, player_col)
+                {
+                    let po_idx = (pull_origin_row * self.width + pull_origin_col) as usize;
+                    let p_idx = (player_row * self.width + player_col) as usize;
+
+                    if self.map[po_idx] != 1 && self.map[p_idx] != 1 && !live_squares[po_idx] {
+                        live_squares[po_idx] = true;
+                        queue.push_back(Point::new(pull_origin_row, pull_origin_col));
+                    }
+                }
+            }
+        }
+
+        for (i, &live) in live_squares.iter().enumerate() {
+            if self.map[i] != 1 && !live {
+                self.dead_squares[i / 64] |= 1u64 << (i % 64);
+            }
+        }
+    }
+
+    fn precompute_squares(&mut self) {
+        self.squares = (0..self.map.len())
+            .map(|idx| {
+                let goal = if (self.goal_grid[idx / 64] & (1u64 << (idx % 64))) != 0 { pattern::GOAL } else { 0 };
+                match self.map[idx] {
+                    1 => pattern::WALL,
+                    _ if (self.dead_squares[idx / 64] & (1u64 << (idx % 64))) != 0 => pattern::DEAD | goal,
+                    _ => pattern::FLOOR | goal,
+                }
+            })
+            .collect();
+    }
+
+    pub(crate) fn learn(&mut self, deadlocks: &[LearnedDeadlock]) {
+        for deadlock in deadlocks {
+            self.corrals.learn(deadlock);
+        }
+    }
+
+    fn precompute_reachable_goals(&mut self) {
+        self.reachable_goals = (0..self.map.len())
+            .map(|idx| {
+                (0..self.goals.len())
+                    .filter(|&g| self.push_dist.get(g, idx) != UNREACHABLE)
+                    .fold(0u64, |mask, g| mask | 1u64 << g)
+            })
+            .collect();
+    }
+
+    fn precompute_rooms(&mut self) {
+        self.goal_counts_by_room.clear();
+        let mut current_room_id = 0u8;
+
+        for row in 0..self.height {
+            for col in 0..self.width {
+                let idx = (row * self.width + col) as usize;
+                if self.map[idx] != 1 && self.room_ids[idx] == 255 {
+                    let goal_count = self.flood_fill_room(Point::new(row, col), current_room_id);
+                    self.goal_counts_by_room.push(goal_count);
+                    current_room_id += 1;
+                }
+            }
+        }
+    }
+
+    #[inline(always)]
+    fn is_solved_boxes(&self, boxes: &[Point]) -> bool {
+        for &b in boxes {
+            let idx = self.to_idx(b.row, b.col);
+            if (self.goal_grid[idx / 64] & (1u64 << (idx % 64))) == 0 {
+                return false;
+            }
+        }
+        true
+    }
+
+    #[inline(always)]
+    fn boxes_zobrist_key(&self, boxes: &[Point]) -> u64 {
+        let mut key: u64 = 0;
+        for &b in boxes {
+            let idx = self.to_idx(b.row, b.col);
+            key ^= self.zobrist_table[idx][1];
+        }   
+        key
+    }
+
+    #[inline(always)]
+    fn find_goal_index(&self, row: i16, col: i16) -> Option<usize> {
+        self.goals.iter().position(|g| g.row == row && g.col == col)
+    }
+
+    fn flood_fill_room(&mut self, start: Point, room_id: u8) -> i32 {
+        let mut goal_count = 0;
+        let mut queue = std::collections::VecDeque::with_capacity(100);
+        queue.push_back(start);
+        let start_idx = (start.row * self.width + start.col) as usize;
+        self.room_ids[start_idx] = room_id;
+
+        while let Some(current) = queue.pop_front() {
+            let idx = (current.row * self.width + current.col) as usize;
+            if (self.goal_grid[idx / 64] & (1u64 << (idx % 64))) != 0 {
+                goal_count += 1;
+            }
+
+            for &(drow, dcol) in &DIR_OFFSETS {
+                let new_row = current.row + drow as i16;
+                let new_col = current.col + dcol as i16;
+
+                if self.is_valid(new_row, new_col) {
+                    let new_idx = (new_row * self.width + new_col) as usize;
+                    if self.map[new_idx] != 1 && self.room_ids[new_idx] == 255 {
+                        self.room_ids[new_idx] = room_id;
+                        queue.push_back(Point::new(new_row, new_col));
+                    }
+                }
+            }
+        }
+
+        goal_count
+    }
+
+    #[inline(always)]
+    fn is_valid(&self, row: i16, col: i16) -> bool {
+        row >= 0 && row < self.height && col >= 0 && col < self.width
+    }
+
+    #[inline(always)]
+    fn to_idx(&self, row: i16, col: i16) -> usize {
+        (row * self.width + col) as usize
+    }
+
+    /// Box hash after moving one box from `old_box` to `new_box`.
+    #[inline(always)]
+    fn calculate_zobrist_hash_incremental(&self, old_hash: u64, old_box: Point, new_box: Point) -> u64 {
+        let old_b_idx = self.to_idx(old_box.row, old_box.col);
+        let new_b_idx = self.to_idx(new_box.row, new_box.col);
+        old_hash ^ self.zobrist_table[old_b_idx][1] ^ self.zobrist_table[new_b_idx][1]
+    }
+
+    /// Closed-set key: the box hash combined with the player's key square.
+    #[inline(always)]
+    fn calculate_zobrist_hash(&self, box_hash: u64, player: Point) -> u64 {
+        let p_idx = self.to_idx(player.row, player.col);
+        box_hash ^ self.zobrist_table[p_idx][0]
+    }
+
+    /// Exact closed-set key for `boxes` with the player's key square.
+    fn state_key(&self, boxes: &[Point], player: Point) -> StateKey {
+        let mut key: StateKey = SmallVec::from_elem(0, self.floor_words + 1);
+        for b in boxes {
+            let bit = self.floor_index[self.to_idx(b.row, b.col)] as usize;
+            key[bit / 64] |= 1u64 << (bit % 64);
+        }
+        key[self.floor_words] = self.to_idx(player.row, player.col) as u64;
+        key
+    }
+
+    fn calculate_heuristic(&self, boxes: &[Point]) -> i32 {
+        let mut total_dist = 0;
+        let mut used_goal_mask: u64 = 0; // bitmask instead of ArrayVec<bool,32>
+        let mut boxes_on_goals = 0;
+
+        for &box_pos in boxes {
+            let idx = self.to_idx(box_pos.row, box_pos.col);
+
+            if (self.goal_grid[idx / 64] & (1u64 << (idx % 64))) != 0 {
+                if let Some(goal_index) = self.find_goal_index(box_pos.row, box_pos.col) {
+                    used_goal_mask |= 1u64 << goal_index;
+                }
+                boxes_on_goals += 1;
+                continue;
+            }
+
+            let mut min_dist = i32::MAX;
+            let mut best_idx: Option<usize> = None;
+
+            for (i, goal) in self.goals.iter().enumerate() {
+                if (used_goal_mask & (1u64 << i)) == 0 {
+                    let dist = (box_pos.row - goal.row).abs() as i32
+                        + (box_pos.col - goal.col).abs() as i32;
+                    if dist < min_dist {
+                        min_dist = dist;
+                        best_idx = Some(i);
+                    }
+                }
+            }
+
+            if let Some(i) = best_idx {
+                used_goal_mask |= 1u64 << i;
+                total_dist += min_dist;
+            }
+        }
+
+        if boxes_on_goals == boxes.len() {
+            return 0;
+        }
+
+        total_dist
+    }
+
+    /// Admissible and consistent: each box needs at least its push distance
+    /// to the nearest goal, and one push changes that by at most one.
+    fn calculate_lower_bound(&self, boxes: &[Point]) -> i32 {
+        let mut total = 0;
+        for &box_pos in boxes {
+            let idx = self.to_idx(box_pos.row, box_pos.col);
+            match self.push_dist.nearest(self.goals.len(), idx) {
+                UNREACHABLE => return DEAD_END,
+                dist => total += dist as i32,
+            }
+        }
+        total
+    }
+
+    /// Minimum-cost assignment of boxes to distinct goals by push distance.
+    /// Still a lower bound on pushes, and never smaller than
+    /// `calculate_lower_bound`.
+    fn calculate_matching(&self, boxes: &[Point]) -> i32 {
+        let cost = |b: usize, g: usize| {
+            let idx = self.to_idx(boxes[b].row, boxes[b].col);
+            match self.push_dist.get(g, idx) {
+                UNREACHABLE => None,
+                dist => Some(dist as i32),
+            }
+        };
+        min_cost_matching(boxes.len(), cost).unwrap_or(DEAD_END)
+    }
+
+    /// Heuristic for a box layout, through the transposition table.
+    #[inline(always)]
+    fn evaluate(&mut self, options: &SolveOptions, boxes: &[Point], box_hash: u64) -> i32 {
+        if let Some(cached) = self.tt.probe(box_hash) {
+            self.stats.tt_hits += 1;
+            return cached;
+        }
+        self.stats.tt_misses += 1;
+
+        let h = match (options.heuristic, options.objective) {
+            (Heuristic::Matching, _) => self.calculate_matching(boxes),
+            (Heuristic::Greedy, Objective::Any) => self.calculate_heuristic(boxes),
+            (Heuristic::Greedy, _) => self.calculate_lower_bound(boxes),
+        };
+        self.tt.store(box_hash, h);
+        h
+    }
+
+    #[inline(always)]
+    fn is_wall_at(&self, row: i16, col: i16) -> bool {
+        !self.is_valid(row, col) || self.map[self.to_idx(row, col)] == 1
+    }
+
+    #[inline(always)]
+    fn is_dead_at(&self, row: i16, col: i16) -> bool {
+        let idx = self.to_idx(row, col);
+        (self.dead_squares[idx / 64] & (1u64 << (idx % 64))) != 0
+    }
+
+    /// Whether the box at `pos` can never move again: along each axis it has
+    /// a wall on one side, dead squares on both, or a neighbouring box that
+    /// is frozen itself. `checking` holds the boxes whose check is in
+    /// progress; they count as walls, so a group of boxes that only block
+    /// each other is frozen as a whole. Returns `Some(true)` if the box and
+    /// every box freezing it stand on goals, `Some(false)` if one does not,
+    /// and `None` if the box can still move.
+    fn frozen(&self, boxes: &[Point], pos: Point, checking: &mut SmallVec<[Point; 8]>) -> Option<bool> {
+        checking.push(pos);
+        let mut on_goals = self.is_solved_boxes(&[pos]);
+        for (drow, dcol) in [(1, 0), (0, 1)] {
+            let sides = [Point::new(pos.row - drow, pos.col - dcol), Point::new(pos.row + drow, pos.col + dcol)];
+            let blocked_by_map = sides.iter().any(|s| self.is_wall_at(s.row, s.col) || checking.contains(s))
+                || sides.iter().all(|s| self.is_dead_at(s.row, s.col));
+            if blocked_by_map {
+                continue;
+            }
+            let neighbour = sides
+                .iter()
+                .filter(|s| boxes.contains(s))
+                .find_map(|&s| self.frozen(boxes, s, checking));
+            match neighbour {
+                Some(neighbour_on_goals) => on_goals &= neighbour_on_goals,
+                None => {
+                    checking.pop();
+                    return None;
+                }
+            }
+        }
+        checking.pop();
+        Some(on_goals)
+    }
+
+    /// Whether a deadlock pattern matches around the box just pushed to `pos`.
+    fn is_pattern_deadlock(&self, patterns: &Patterns, boxes: &[Point], pos: Point) -> bool {
+        if patterns.is_empty() {
+            return false;
+        }
+        let mut area: Area = std::array::from_fn(|i| {
+            let (row, col) = (pos.row + i as i16 / 5 - 2, pos.col + i as i16 % 5 - 2);
+            if self.is_valid(row, col) {
+                self.squares[self.to_idx(row, col)]
+            } else {
+                pattern::WALL
+            }
+        });
+        for b in boxes {
+            let (row, col) = (b.row - pos.row + 2, b.col - pos.col + 2);
+            if (0..5).contains(&row) && (0..5).contains(&col) {
+                let square = &mut area[(row * 5 + col) as usize];
+                *square = (*square & pattern::GOAL) | pattern::BOX;
+            }
+        }
+        patterns.deadlock_around(&area)
+    }
+
+    /// Whether the boxes cannot each be given a different goal that it can
+    /// still be pushed to, counting walls only.
+    fn is_matching_deadlock(&self, boxes: &[Point]) -> bool {
+        let allowed: SmallVec<[u64; 20]> =
+            boxes.iter().map(|b| self.reachable_goals[self.to_idx(b.row, b.col)]).collect();
+        !has_perfect_matching(&allowed)
+    }
+
+    /// Whether the box just pushed to `pos`, or a box it froze in turn, is
+    /// frozen off a goal, which no later push can undo. Only boxes next to a
+    /// frozen one can have been frozen by the push, so the check spreads
+    /// from `pos` through frozen boxes alone.
+    fn is_freeze_deadlock(&self, boxes: &[Point], pos: Point) -> bool {
+        let mut visited: SmallVec<[Point; 8]> = SmallVec::from_elem(pos, 1);
+        let mut stack: SmallVec<[Point; 8]> = SmallVec::from_elem(pos, 1);
+        while let Some(b) = stack.pop() {
+            match self.frozen(boxes, b, &mut SmallVec::new()) {
+                Some(false) => return true,
+                Some(true) => {
+                    for &(drow, dcol) in &DIR_OFFSETS {
+                        let next = Point::new(b.row + drow as i16, b.col + dcol as i16);
+                        if boxes.contains(&next) && !visited.contains(&next) {
+                            visited.push(next);
+                            stack.push(next);
+                        }
+                    }
+                }
+                None => {}
+            }
+        }
+        false
+    }
+
+    #[inline]
+    fn is_room_deadlock(&self, boxes: &[Point]) -> bool {
+        let mut box_counts: SmallVec<[i32; 8]> = SmallVec::new();
+        box_counts.resize(self.goal_counts_by_room.len(), 0);
+
+        for box_pos in boxes {
+            let idx = self.to_idx(box_pos.row, box_pos.col);
+            let room_id = self.room_ids[idx];
+            if room_id != 255 {
+                box_counts[room_id as usize] += 1;
+            }
+        }
+
+        for (room_id, &goal_count) in self.goal_counts_by_room.iter().enumerate() {
+            if box_counts[room_id] > goal_count {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// Push-level search. Each expansion floods the player's reachable
+    /// region once and generates every legal push from anywhere in it, so
+    /// walking never creates states of its own. `stop` is an extra cancel
+    /// flag, used by the portfolio to halt the searches that lost the race.
+    pub(crate) fn solve(
+        &mut self,
+        start_player: Point,
+        start_boxes: BoxVec,
+        options: &SolveOptions,
+        strategy: Strategy,
+        stop: Option<&AtomicBool>,
+    ) -> Result<Solution, SolveError> {
+        let started = Instant::now();
+        let objective = options.objective;
+        let exact_player = objective.counts_moves();
+        let initial_boxes = start_boxes.clone();
+        self.stats = SolveStats::default();
+        if options.seed != self.zobrist_seed {
+            self.initialize_zobrist(options.seed);
+        }
+        self.tt.resize(options.tt_size);
+        self.tt.next_age();
+
+        let start_box_hash = self.boxes_zobrist_key(&start_boxes);
+        let start_heuristic = self.evaluate(options, &start_boxes, start_box_hash);
+        self.stats.start_heuristic = start_heuristic;
+        let frozen = start_boxes.iter().any(|&b| self.is_freeze_deadlock(&start_boxes, b));
+        if frozen || self.is_matching_deadlock(&start_boxes) {
+            return Err(SolveError::Unsolvable);
+        }
+
+        let start_state = State {
+            boxes: start_boxes,
+            player: start_player,
+            node: ROOT,
+            pushes: 0,
+            moves: 0,
+            heuristic: start_heuristic,
+            priority: strategy.priority(objective, 0, 0, start_heuristic),
+            box_hash: start_box_hash,
+        };
+
+        let mut nodes: Vec<PushNode> = Vec::with_capacity(100000);
+        let mut open_set = BinaryHeap::with_capacity(10000);
+        let mut visited = ClosedSet::new(options.closed_set, 200000);
+
+        // Expanded state closest to solved, reported if a limit stops us.
+        let mut best = (start_state.heuristic, ROOT, start_state.boxes.clone());
+
+        open_set.push(start_state);
+
+        while let Some(current) = open_set.pop() {
+            if self.is_solved_boxes(&current.boxes) {
+                let pushes = Self::collect_pushes(&nodes, current.node);
+                let moves = self.reconstruct_moves(start_player, &initial_boxes, &pushes);
+                return Ok(Solution {
+                    moves: to_lurd(&moves),
+                    pushes: pushes.len(),
+                    stats: self.stats.clone(),
+                });
+            }
+
+            if let Some(limit) = self.check_limits(options, stop, started, open_set.capacity(), nodes.capacity(), &visited) {
+                let pushes = Self::collect_pushes(&nodes, best.1);
+                let partial = Partial {
+                    moves: to_lurd(&self.reconstruct_moves(start_player, &initial_boxes, &pushes)),
+                    pushes: pushes.len(),
+                    boxes_on_goals: best.2.iter().filter(|b| self.is_solved_boxes(&[**b])).count(),
+                    stats: self.stats.clone(),
+                };
+                return Err(limit(Box::new(partial)));
+            }
+
+            self.reach.compute(&self.map, &current.boxes, current.player);
+            let player_key = if exact_player { current.player } else { self.reach.top_left() };
+            let inserted = visited.insert(self.calculate_zobrist_hash(current.box_hash, player_key), || {
+                self.state_key(&current.boxes, player_key)
+            });
+            self.stats.hash_collisions = visited.collisions();
+            self.stats.closed_set_size = visited.len() as u64;
+            if !inserted {
+                continue;
+            }
+
+            if self.corrals.is_learned_deadlock(&current.boxes, &self.reach) {
+                self.stats.learned_deadlocks += 1;
+                continue;
+            }
+
+            if self.corrals.is_deadlock(&self.map, &self.squares, &current.boxes, &self.reach) {
+                self.stats.corral_deadlocks += 1;
+                continue;
+            }
+
+            self.stats.nodes_expanded += 1;
+            if current.heuristic < best.0 {
+                best = (current.heuristic, current.node, current.boxes.clone());
+            }
+            if let Some(progress) = options.progress.as_deref() {
+                if self.stats.nodes_expanded.is_multiple_of(SAMPLE_INTERVAL) {
+                    progress.record(&self.stats, best.0);
+                }
+            }
+
+            for (box_idx, &box_pos) in current.boxes.iter().enumerate() {
+                for (dir, &(drow, dcol)) in DIR_OFFSETS.iter().enumerate() {
+                    let origin = Point::new(box_pos.row - drow as i16, box_pos.col - dcol as i16);
+                    if !self.reach.is_reachable(origin) {
+                        continue;
+                    }
+
+                    let push_row = box_pos.row + drow as i16;
+                    let push_col = box_pos.col + dcol as i16;
+
+                    if !self.is_valid(push_row, push_col) {
+                        continue;
+                    }
+
+                    let push_idx = self.to_idx(push_row, push_col);
+                    if self.map[push_idx] == 1 {
+                        continue;
+                    }
+
+                    let push_pos = Point::new(push_row, push_col);
+                    if current.boxes.iter().any(|b| b.row == push_row && b.col == push_col) {
+                        continue;
+                    }
+
+                    if (self.dead_squares[push_idx / 64] & (1u64 << (push_idx % 64))) != 0 {
+                        continue;
+                    }
+
+                    let mut new_boxes = current.boxes.clone();
+                    new_boxes[box_idx] = push_pos;
+
+                    if self.is_room_deadlock(&new_boxes) {
+                        continue;
+                    }
+
+                    if self.is_freeze_deadlock(&new_boxes, push_pos) {
+                        self.stats.freeze_deadlocks += 1;
+                        continue;
+                    }
+
+                    if self.is_pattern_deadlock(&options.patterns, &new_boxes, push_pos) {
+                        self.stats.pattern_deadlocks += 1;
+                        continue;
+                    }
+
+                    if self.is_matching_deadlock(&new_boxes) {
+                        self.stats.matching_deadlocks += 1;
+                        continue;
+                    }
+
+                    let new_box_hash = self.calculate_zobrist_hash_incremental(current.box_hash, box_pos, push_pos);
+
+                    // Without exact player keys the child's region is only
+                    // known once it is expanded; duplicates are caught on pop.
+                    if exact_player
+                        && visited.contains(self.calculate_zobrist_hash(new_box_hash, box_pos), || {
+                            self.state_key(&new_boxes, box_pos)
+                        })
+                    {
+                        continue;
+                    }
+
+                    let new_heuristic = self.evaluate(options, &new_boxes, new_box_hash);
+
+                    let new_pushes = current.pushes + 1;
+                    let new_moves = current.moves + self.reach.distance(origin) as u32 + 1;
+                    nodes.push(PushNode {
+                        parent: current.node,
+                        box_pos,
+                        dir: dir as u8,
+                    });
+
+                    let next_state = State {
+                        priority: strategy.priority(objective, new_moves as i32, new_pushes as i32, new_heuristic),
+                        boxes: new_boxes,
+                        player: box_pos,
+                        node: (nodes.len() - 1) as u32,
+                        pushes: new_pushes,
+                        moves: new_moves,
+                        heuristic: new_heuristic,
+                        box_hash: new_box_hash,
+                    };
+
+                    open_set.push(next_state);
+                    self.stats.nodes_generated += 1;
+                }
+            }
+            self.stats.peak_open_set = self.stats.peak_open_set.max(open_set.len() as u64);
+        }
+
+        Err(SolveError::Unsolvable)
+    }
+
+    /// Returns the error constructor for the first limit exceeded, if any.
+    /// Cancellation, time and memory are only sampled every `SAMPLE_INTERVAL`
+    /// expansions.
+    fn check_limits(
+        &self,
+        options: &SolveOptions,
+        stop: Option<&AtomicBool>,
+        started: Instant,
+        open_capacity: usize,
+        node_capacity: usize,
+        visited: &ClosedSet,
+    ) -> Option<fn(Box<Partial>) -> SolveError> {
+        if options.max_nodes.is_some_and(|max| self.stats.nodes_expanded >= max) {
+            return Some(SolveError::NodeLimit);
+        }
+        if !self.stats.nodes_expanded.is_multiple_of(SAMPLE_INTERVAL) {
+            return None;
+        }
+        let cancelled = |flag: &AtomicBool| flag.load(AtomicOrdering::Relaxed);
+        if options.cancel.as_deref().is_some_and(cancelled) || stop.is_some_and(cancelled) {
+            return Some(|_| SolveError::Cancelled);
+        }
+        if options.time_limit.is_some_and(|limit| started.elapsed() >= limit) {
+            return Some(SolveError::Timeout);
+        }
+        if let Some(max) = options.max_memory {
+            let used = open_capacity * size_of::<State>()
+                + node_capacity * size_of::<PushNode>()
+                + visited.approx_bytes()
+                + self.tt.size * size_of::<(u64, i32, u8)>();
+            if used >= max {
+                return Some(SolveError::OutOfMemory);
+            }
+        }
+        None
+    }
+
+    /// Pushes from the root to `node`, in order.
+    fn collect_pushes(nodes: &[PushNode], mut node: u32) -> Vec<(Point, u8)> {
+        let mut pushes = Vec::new();
+        while node != ROOT {
+            let n = nodes[node as usize];
+            pushes.push((n.box_pos, n.dir));
+            node = n.parent;
+        }
+        pushes.reverse();
+        pushes
+    }
+
+    /// Replays a push sequence, filling in the shortest walk before each push.
+    fn reconstruct_moves(&mut self, start_player: Point, start_boxes: &[Point], pushes: &[(Point, u8)]) -> Vec<Move> {
+        let mut boxes: BoxVec = start_boxes.iter().copied().collect();
+        let mut player = start_player;
+        let mut moves = Vec::new();
+
+        for &(box_pos, dir) in pushes {
+            let (drow, dcol) = DIR_OFFSETS[dir as usize];
+            let origin = Point::new(box_pos.row - drow as i16, box_pos.col - dcol as i16);
+
+            self.reach.compute(&self.map, &boxes, player);
+            moves.extend(self.reach.walk_to(origin).iter().map(|&d| Move::walk(Direction::from_index(d))));
+            moves.push(Move::push(Direction::from_index(dir)));
+
+            if let Some(b) = boxes.iter_mut().find(|b| **b == box_pos) {
+                *b = Point::new(box_pos.row + drow as i16, box_pos.col + dcol as i16);
+            }
+            player = box_pos;
+        }
+
+        moves
+    }
+}
+
+/// Options controlling a single search.
+#[derive(Clone, Debug)]
+pub struct SolveOptions {
+    pub objective: Objective,
+    pub heuristic: Heuristic,
+    /// Heuristic cache entries (16 bytes each); 0 disables the cache.
+    pub tt_size: usize,
+    /// How the closed set tells states apart.
+    pub closed_set: ClosedSetMode,
+    /// Stop with `SolveError::Timeout` after this much wall-clock time.
+    pub time_limit: Option<Duration>,
+    /// Stop with `SolveError::NodeLimit` after expanding this many states.
+    pub max_nodes: Option<u64>,
+    /// Stop with `SolveError::OutOfMemory` once the search's main tables
+    /// (open set, node arena, closed set, heuristic cache) reach roughly
+    /// this many bytes.
+    pub max_memory: Option<usize>,
+    /// Seed for the Zobrist keys. The same seed gives the same search, node
+    /// for node; another seed changes only hash-dependent behaviour such as
+    /// which states collide and which cache entries evict each other.
+    pub seed: u64,
+    /// Searches to run in parallel. 1 (the default) runs exactly the search
+    /// described by the other options; more runs a portfolio of differently
+    /// configured searches and returns whichever finishes first.
+    pub threads: usize,
+    /// Stop with `SolveError::Cancelled` once this flag is set.
+    pub cancel: Option<Arc<AtomicBool>>,
+    /// Counters the search keeps current for another thread to watch.
+    pub progress: Option<Arc<Progress>>,
+    /// Deadlock patterns checked after every push.
+    pub patterns: Arc<Patterns>,
+}
+
+impl Default for SolveOptions {
+    fn default() -> Self {
+        SolveOptions {
+            objective: Objective::default(),
+            heuristic: Heuristic::default(),
+            tt_size: DEFAULT_TT_SIZE,
+            closed_set: ClosedSetMode::default(),
+            time_limit: None,
+            max_nodes: None,
+            max_memory: None,
+            seed: DEFAULT_SEED,
+            threads: 1,
+            cancel: None,
+            progress: None,
+            patterns: Patterns::builtin(),
+        }
+    }
+}
+
+pub const DEFAULT_TT_SIZE: usize = 1 << 20;
+pub const DEFAULT_SEED: u64 = 0x5EED_50C0_BA11_2024;
+
+/// Counters from the most recent search.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SolveStats {
+    /// Heuristic evaluations answered from the transposition table.
+    pub tt_hits: u64,
+    /// Heuristic evaluations that had to be computed.
+    pub tt_misses: u64,
+    /// Distinct states that shared a Zobrist hash with an earlier one. Only
+    /// detected with `ClosedSetMode::Exact`.
+    pub hash_collisions: u64,
+    /// States popped from the open set and expanded.
+    pub nodes_expanded: u64,
+    /// Successor states pushed onto the open set.
+    pub nodes_generated: u64,
+    /// Distinct states in the closed set.
+    pub closed_set_size: u64,
+    /// Largest number of states waiting in the open set at once.
+    pub peak_open_set: u64,
+    /// Heuristic value of the start state.
+    pub start_heuristic: i32,
+    /// Pushes pruned because they froze a box off its goal.
+    pub freeze_deadlocks: u64,
+    /// Pushes pruned because a deadlock pattern matched around the box.
+    pub pattern_deadlocks: u64,
+    /// Pushes pruned because the boxes could no longer be matched to
+    /// distinct goals they can each reach.
+    pub matching_deadlocks: u64,
+    /// Positions pruned because the boxes fencing off an area the player
+    /// cannot reach were proven unable to reach goals.
+    pub corral_deadlocks: u64,
+    /// Positions pruned because they hold the boxes of a learned deadlock:
+    /// one proven earlier in this search or given to [`Solver::learn`].
+    pub learned_deadlocks: u64,
+}
+
+/// Live counters of a running search, refreshed every 1024 expansions.
+///
+/// Share one through `SolveOptions::progress` and read it from another
+/// thread. The members of a portfolio all report into it; each counter holds
+/// the furthest any member has got.
+#[derive(Debug)]
+pub struct Progress {
+    nodes_expanded: AtomicU64,
+    nodes_generated: AtomicU64,
+    best_heuristic: AtomicI32,
+}
+
+impl Default for Progress {
+    fn default() -> Self {
+        Progress {
+            nodes_expanded: AtomicU64::new(0),
+            nodes_generated: AtomicU64::new(0),
+            best_heuristic: AtomicI32::new(i32::MAX),
+        }
+    }
+}
+
+impl Progress {
+    pub fn nodes_expanded(&self) -> u64 {
+        self.nodes_expanded.load(AtomicOrdering::Relaxed)
+    }
+
+    pub fn nodes_generated(&self) -> u64 {
+        self.nodes_generated.load(AtomicOrdering::Relaxed)
+    }
+
+    /// Lowest heuristic value of an expanded state, once one was recorded.
+    pub fn best_heuristic(&self) -> Option<i32> {
+        Some(self.best_heuristic.load(AtomicOrdering::Relaxed)).filter(|&h| h != i32::MAX)
+    }
+
+    fn record(&self, stats: &SolveStats, best_heuristic: i32) {
+        self.nodes_expanded.fetch_max(stats.nodes_expanded, AtomicOrdering::Relaxed);
+        self.nodes_generated.fetch_max(stats.nodes_generated, AtomicOrdering::Relaxed);
+        self.best_heuristic.fetch_min(best_heuristic, AtomicOrdering::Relaxed);
+    }
+}
+
+/// A solved puzzle.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Solution {
+    /// Player moves in LURD notation: `u`, `d`, `l`, `r` for a step and the
+    /// uppercase letter for a push.
+    pub moves: String,
+    /// How many of `moves` pushed a box (the uppercase ones).
+    pub pushes: usize,
+    pub stats: SolveStats,
+}
+
+/// Best progress made by a search that hit a limit.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Partial {
+    /// Moves (LURD) leading to the expanded state with the lowest heuristic.
+    pub moves: String,
+    pub pushes: usize,
+    /// Boxes on goals in that state.
+    pub boxes_on_goals: usize,
+    pub stats: SolveStats,
+}
+
+/// Solver for a single puzzle.
+///
+/// Construction does the per-map precomputation (Zobrist keys, dead squares,
+/// rooms), so one `Solver` can be reused for several searches on the same map.
+pub struct Solver {
+    engine: SokobanSolver,
+    player: Point,
+    boxes: BoxVec,
+}
+
+impl Solver {
+    pub fn new(puzzle: &Puzzle) -> Self {
+        Solver {
+            engine: SokobanSolver::new(puzzle),
+            player: puzzle.player(),
+            boxes: puzzle.boxes().iter().copied().collect(),
+        }
+    }
+
+    pub fn solve(&mut self, options: &SolveOptions) -> Result<Solution, SolveError> {
+        if options.threads > 1 {
+            return portfolio::solve(&mut self.engine, self.player, &self.boxes, options);
+        }
+        self.engine.solve(self.player, self.boxes.clone(), options, Strategy::Plain, None)
+    }
+
+    /// Adds deadlocks learned on an earlier run of a level with the same
+    /// walls and goals (see [`DeadlockDb::deadlocks`](crate::DeadlockDb::deadlocks)); later searches prune
+    /// every position they match.
+    pub fn learn(&mut self, deadlocks: &[LearnedDeadlock]) {
+        self.engine.learn(deadlocks);
+    }
+
+    /// Deadlocks proven by the searches so far, with those given to `learn`,
+    /// for saving with
+    /// [`DeadlockDb::record`](crate::DeadlockDb::record).
+    pub fn learned(&self) -> &[LearnedDeadlock] {
+        self.engine.corrals.learned()
+    }
+
+    /// Counters from the last call to `solve`, whether or not it succeeded.
+    pub fn stats(&self) -> &SolveStats {
+        &self.engine.stats
+    }
+}
+
+/// Convenience wrapper: precompute, then search once.
+pub fn solve(puzzle: &Puzzle, options: &SolveOptions) -> Result<Solution, SolveError> {
+    Solver::new(puzzle).solve(options)
+}
+
+/// Parses a puzzle held in memory (any notation [`Puzzle::parse`] accepts)
+/// and solves it.
+pub fn solve_str(text: &str, options: &SolveOptions) -> Result<Solution, SolveError> {
+    solve(&Puzzle::parse(text)?, options)
+}
+
@@ -0,0 +1,132 @@
+use std::collections::VecDeque;
+use std::fmt;
+
+use crate::puzzle::Point;
+use crate::solver::MAX_GOALS;
+
+/// A single problem found while validating a puzzle.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Diagnostic {
+    /// The map has no `@` or `+`.
+    NoPlayer,
+    /// A second (or later) player square.
+    ExtraPlayer(Point),
+    /// Box and goal counts differ.
+    CountMismatch { boxes: usize, goals: usize },
+    /// The player can walk off the edge of the map at this square.
+    OpenBoundary(Point),
+    /// A box off-goal that the player can never reach.
+    UnreachableBox(Point),
+    /// An empty goal that the player can never reach.
+    UnreachableGoal(Point),
+    /// More goals than the solver's goal bitmasks can hold.
+    TooManyGoals(usize),
+}
+
+impl Diagnostic {
+    /// Square the problem refers to, if any (0-based).
+    pub fn position(&self) -> Option<Point> {
+        match *self {
+            Diagnostic::ExtraPlayer(p)
+            | Diagnostic::OpenBoundary(p)
+            | Diagnostic::UnreachableBox(p)
+            | Diagnostic::UnreachableGoal(p) => Some(p),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(p) = self.position() {
+            write!(f, "line {}, column {}: ", p.row + 1, p.col + 1)?;
+        }
+        match self {
+            Diagnostic::NoPlayer => write!(f, "no player ('@' or '+') on the map"),
+            Diagnostic::ExtraPlayer(_) => write!(f, "more than one player"),
+            Diagnostic::CountMismatch { boxes, goals } => {
+                write!(f, "{} boxes but {} goals", boxes, goals)
+            }
+            Diagnostic::OpenBoundary(_) => write!(f, "player can walk off the map here"),
+            Diagnostic::UnreachableBox(_) => write!(f, "box is outside the player's area"),
+            Diagnostic::UnreachableGoal(_) => write!(f, "goal is outside the player's area"),
+            Diagnostic::TooManyGoals(n) => {
+                write!(f, "{} goals, at most {} are supported", n, MAX_GOALS)
+            }
+        }
+    }
+}
+
+/// Checks everything the search assumes about a map. Returns every problem
+/// found, in reading order within each category; empty means valid.
+pub(crate) fn validate(
+    width: i16,
+    height: i16,
+    map: &[u8],
+    players: &[Point],
+    boxes: &[Point],
+    goals: &[Point],
+) -> Vec<Diagnostic> {
+    let mut problems = Vec::new();
+
+    match players {
+        [] => problems.push(Diagnostic::NoPlayer),
+        [_, extra @ ..] => problems.extend(extra.iter().map(|&p| Diagnostic::ExtraPlayer(p))),
+    }
+
+    if boxes.len() != goals.len() {
+        problems.push(Diagnostic::CountMismatch {
+            boxes: boxes.len(),
+            goals: goals.len(),
+        });
+    }
+
+    if goals.len() > MAX_GOALS {
+        problems.push(Diagnostic::TooManyGoals(goals.len()));
+    }
+
+    let Some(&player) = players.first() else {
+        return problems;
+    };
+
+    // Everything the player could ever stand on, treating boxes as movable.
+    let to_idx = |p: Point| (p.row * width + p.col) as usize;
+    let mut reachable = vec![false; map.len()];
+    let mut queue = VecDeque::new();
+    reachable[to_idx(player)] = true;
+    queue.push_back(player);
+
+    while let Some(current) = queue.pop_front() {
+        if current.row == 0 || current.row == height - 1 || current.col == 0 || current.col == width - 1 {
+            problems.push(Diagnostic::OpenBoundary(current));
+            break;
+        }
+        for (drow, dcol) in [(-1, 0), (1, 0), (0, -1), (0, 1)] {
+            let next = Point::new(current.row + drow, current.col + dcol);
+            let idx = to_idx(next);
+            if map[idx] != 1 && !reachable[idx] {
+                reachable[idx] = true;
+                queue.push_back(next);
+            }
+        }
+    }
+
+    // With an open boundary the reachable set is incomplete; further
+    // reachability reports would only be noise.
+    if matches!(problems.last(), Some(Diagnostic::OpenBoundary(_))) {
+        return problems;
+    }
+
+    for &b in boxes {
+        if !reachable[to_idx(b)] && map[to_idx(b)] != 2 {
+            problems.push(Diagnostic::UnreachableBox(b));
+        }
+    }
+    for &g in goals {
+        if !reachable[to_idx(g)] && !boxes.contains(&g) {
+            problems.push(Diagnostic::UnreachableGoal(g));
+        }
+    }
+
+    problems
+}
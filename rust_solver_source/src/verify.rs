@@ -0,0 +1,110 @@
+use std::fmt;
+
+use crate::moves::Move;
+use crate::puzzle::{Point, Puzzle};
+
+/// Result of replaying a move string that contained no illegal move.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Replay {
+    pub moves: usize,
+    pub pushes: usize,
+    /// Boxes standing on goals after the last move.
+    pub boxes_on_goals: usize,
+    /// Every box ends on a goal.
+    pub solved: bool,
+}
+
+/// Why a move could not be played.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IllegalReason {
+    /// Not one of `udlrUDLR`.
+    UnknownCharacter(char),
+    /// The player would walk into a wall.
+    Wall,
+    /// The box in the way is backed by a wall or another box.
+    BoxBlocked,
+    /// Written as a push (uppercase) but no box is in the way.
+    NotAPush,
+    /// Pushes a box but is written in lowercase. Only checked when the
+    /// string marks pushes at all, so all-lowercase solutions still replay.
+    UnmarkedPush,
+}
+
+/// The first move of a replay that cannot be played.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IllegalMove {
+    /// 0-based position among the moves, whitespace not counted.
+    pub index: usize,
+    pub character: char,
+    /// Player square before the move (0-based).
+    pub position: Point,
+    pub reason: IllegalReason,
+    /// Pushes made by the legal moves before it.
+    pub pushes: usize,
+}
+
+impl fmt::Display for IllegalMove {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "move {} ('{}') from line {}, column {}: ",
+            self.index + 1,
+            self.character,
+            self.position.row + 1,
+            self.position.col + 1
+        )?;
+        match self.reason {
+            IllegalReason::UnknownCharacter(_) => write!(f, "not a LURD move"),
+            IllegalReason::Wall => write!(f, "walks into a wall"),
+            IllegalReason::BoxBlocked => write!(f, "pushes a box into a wall or another box"),
+            IllegalReason::NotAPush => write!(f, "marked as a push but there is no box to push"),
+            IllegalReason::UnmarkedPush => write!(f, "pushes a box but is not marked as a push"),
+        }
+    }
+}
+
+/// Replays `moves` (LURD; whitespace is ignored) on `puzzle` and reports the
+/// final state, or the first move that cannot be played.
+pub fn verify(puzzle: &Puzzle, moves: &str) -> Result<Replay, IllegalMove> {
+    let mut boxes = puzzle.boxes().to_vec();
+    let mut player = puzzle.player();
+    let marks_pushes = moves.chars().any(|c| c.is_ascii_uppercase());
+    let mut count = 0;
+    let mut pushes = 0;
+
+    for (index, character) in moves.chars().filter(|c| !c.is_whitespace()).enumerate() {
+        let illegal = |reason| IllegalMove { index, character, position: player, reason, pushes };
+        let step = Move::from_char(character).ok_or_else(|| illegal(IllegalReason::UnknownCharacter(character)))?;
+        let (drow, dcol) = step.direction.offset();
+        let next = Point::new(player.row + drow, player.col + dcol);
+        if puzzle.is_wall(next) {
+            return Err(illegal(IllegalReason::Wall));
+        }
+
+        match boxes.iter().position(|&b| b == next) {
+            Some(pushed) => {
+                let beyond = Point::new(next.row + drow, next.col + dcol);
+                if puzzle.is_wall(beyond) || boxes.contains(&beyond) {
+                    return Err(illegal(IllegalReason::BoxBlocked));
+                }
+                if marks_pushes && !step.push {
+                    return Err(illegal(IllegalReason::UnmarkedPush));
+                }
+                boxes[pushed] = beyond;
+                pushes += 1;
+            }
+            None if step.push => return Err(illegal(IllegalReason::NotAPush)),
+            None => {}
+        }
+        player = next;
+        count += 1;
+    }
+
+    let boxes_on_goals = boxes.iter().filter(|&&b| puzzle.is_goal(b)).count();
+    Ok(Replay {
+        moves: count,
+        pushes,
+        boxes_on_goals,
+        solved: boxes_on_goals == boxes.len(),
+    })
+}
@@ -0,0 +1,295 @@
+use serde_json::Value;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Command, Output, Stdio};
+
+fn run_solver(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_rust_solver")).args(args).output().unwrap()
+}
+
+fn run_solver_with_stdin(args: &[&str], input: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rust_solver"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn map_path(name: &str) -> String {
+    format!("{}/../maps/{}.txt", env!("CARGO_MANIFEST_DIR"), name)
+}
+
+#[test]
+fn json_report_for_a_solved_map() {
+    let output = run_solver(&["--format", "json", "--objective", "pushes", &map_path("twoboxes1")]);
+    assert!(output.status.success());
+    let report: Value = serde_json::from_slice(&output.stdout).unwrap();
+
+    assert_eq!(report["termination"], "solved");
+    assert_eq!(report["pushes"], 9);
+    let solution = report["solution"].as_str().unwrap();
+    assert_eq!(report["moves"].as_u64().unwrap() as usize, solution.len());
+    assert!(report["stats"]["nodes_expanded"].as_u64().unwrap() > 0);
+    assert!(report["stats"]["peak_open_set"].as_u64().unwrap() > 0);
+    assert!(report["elapsed_ms"].as_f64().is_some());
+    assert_eq!(report["config"]["objective"], "pushes");
+    assert_eq!(report["config"]["threads"], 1);
+    assert_eq!(report["config"]["patterns"], "builtin");
+}
+
+#[test]
+fn json_report_for_failures_keeps_exit_codes() {
+    let output = run_solver(&["--format", "json", "--max-nodes", "10", &map_path("fiveboxes3")]);
+    assert_eq!(output.status.code(), Some(9));
+    let report: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["termination"], "node-limit");
+    assert!(report["solution"].is_null());
+    assert_eq!(report["stats"]["nodes_expanded"], 10);
+    assert!(report["best_state"]["pushes"].as_u64().is_some());
+
+    let output = run_solver(&["--format", "json", "missing.txt"]);
+    assert_eq!(output.status.code(), Some(2));
+    let report: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["termination"], "io");
+    assert!(report["stats"].is_null());
+}
+
+#[test]
+fn verify_subcommand_checks_solutions() {
+    let map = map_path("twoboxes1");
+    let solved = run_solver(&[&map]);
+    let moves = String::from_utf8(solved.stdout).unwrap();
+
+    let output = run_solver(&["verify", &map, moves.trim()]);
+    assert!(output.status.success());
+    let text = String::from_utf8(output.stdout).unwrap();
+    assert!(text.contains("pushes: 9"), "{}", text);
+    assert!(text.contains("solved: yes"), "{}", text);
+
+    let output = run_solver(&["verify", &map, "uuu"]);
+    assert_eq!(output.status.code(), Some(10));
+    assert!(String::from_utf8(output.stderr).unwrap().contains("illegal move 1"));
+
+    let output = run_solver(&["verify", &map, &moves.trim()[..5]]);
+    assert_eq!(output.status.code(), Some(10));
+    assert!(String::from_utf8(output.stdout).unwrap().contains("solved: no"));
+}
+
+#[test]
+fn batch_solves_a_collection_and_writes_reports() {
+    let dir = std::env::temp_dir().join(format!("rust_solver_batch_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let collection = dir.join("pair.sok");
+    fs::write(&collection, "Title: one\n#####\n#@$.#\n#####\n\nTitle: two\n######\n#@ $ #\n#.   #\n######\n").unwrap();
+    let csv = dir.join("report.csv");
+    let json = dir.join("report.json");
+
+    let output = run_solver(&["batch", "--report", csv.to_str().unwrap(), collection.to_str().unwrap()]);
+    assert!(output.status.success());
+    let table = String::from_utf8(output.stdout).unwrap();
+    assert!(table.contains("solved 1/2"), "{}", table);
+
+    let report = fs::read_to_string(&csv).unwrap();
+    let rows: Vec<&str> = report.lines().collect();
+    assert_eq!(rows[0], "level,title,status,moves,pushes,nodes_expanded,nodes_generated,elapsed_ms");
+    assert!(rows[1].starts_with("pair#1,one,solved,1,1,"), "{}", rows[1]);
+    assert!(rows[2].starts_with("pair#2,two,unsolvable,,,"), "{}", rows[2]);
+
+    let output = run_solver(&["batch", "--jobs", "2", "--report", json.to_str().unwrap(), dir.to_str().unwrap()]);
+    assert!(output.status.success());
+    let report: Value = serde_json::from_str(&fs::read_to_string(&json).unwrap()).unwrap();
+    assert_eq!(report["total"], 2);
+    assert_eq!(report["levels"][0]["solution"], "R");
+    assert_eq!(report["levels"][1]["title"], "two");
+    assert_eq!(report["config"]["time_limit"], 10.0);
+
+    for flag in ["--level", "--lowercase", "--format"] {
+        let output = run_solver(&["batch", flag, "1", dir.to_str().unwrap()]);
+        assert_eq!(output.status.code(), Some(1));
+        assert!(String::from_utf8(output.stderr).unwrap().contains(&format!("batch does not take {}", flag)));
+    }
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn levels_of_a_collection_are_selected_by_index_or_title() {
+    let dir = std::env::temp_dir().join(format!("rust_solver_level_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let collection = dir.join("set.xsb");
+    fs::write(&collection, "; 1\n#####\n#@$.#\n#####\n\n; 2\nTitle: Back\n#####\n#.$@#\n#####\n").unwrap();
+    let path = collection.to_str().unwrap();
+
+    let solve = |args: &[&str]| String::from_utf8(run_solver(args).stdout).unwrap().trim().to_string();
+    assert_eq!(solve(&[path]), "R");
+    assert_eq!(solve(&["--level", "2", path]), "L");
+    assert_eq!(solve(&["--level", "back", path]), "L");
+    assert!(run_solver(&["verify", "--level", "Back", path, "L"]).status.success());
+
+    let output = run_solver(&["--level", "nope", path]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8(output.stderr).unwrap().contains("no level 'nope'"));
+
+    // Bare maps separated by blank lines are a collection too.
+    let bare = dir.join("bare.sok");
+    fs::write(&bare, "#####\n#@$.#\n#####\n\n#####\n#.$@#\n#####\n").unwrap();
+    let bare = bare.to_str().unwrap();
+    assert_eq!(solve(&[bare]), "R");
+    assert_eq!(solve(&["--level", "2", bare]), "L");
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn export_writes_plain_and_rle_maps() {
+    let map = map_path("twoboxes1");
+    let rle = run_solver(&["export", "--rle", &map]);
+    assert!(rle.status.success());
+    let rle = String::from_utf8(rle.stdout).unwrap();
+    assert_eq!(rle.trim(), "5#|#3-2#|#2-$@#|2#$-.#|-#2-.#|-5#");
+
+    let plain = String::from_utf8(run_solver(&["export", &map]).stdout).unwrap();
+    assert_eq!(plain.lines().nth(2), Some("#  $@#"));
+
+    let output = run_solver(&["export", &map, "--level"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8(output.stderr).unwrap().contains("--level needs a value"));
+}
+
+#[test]
+fn deadlock_db_is_kept_between_runs_and_can_be_cleared() {
+    let db = std::env::temp_dir().join(format!("rust_solver_deadlocks_{}.txt", std::process::id()));
+    let db = db.to_str().unwrap();
+    let map = map_path("8boxes5");
+
+    let output = run_solver(&["--deadlock-db", db, "--format", "json", &map]);
+    assert!(output.status.success());
+    let cold: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(cold["config"]["deadlock_db"], db);
+    let output = run_solver(&["--deadlock-db", db, "--format", "json", &map]);
+    let warm: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert!(warm["stats"]["nodes_expanded"].as_u64() < cold["stats"]["nodes_expanded"].as_u64());
+
+    let listing = String::from_utf8(run_solver(&["deadlocks", db]).stdout).unwrap();
+    assert!(listing.contains("levels: 1\n"), "{}", listing);
+    let shown = String::from_utf8(run_solver(&["deadlocks", db, &map]).stdout).unwrap();
+    assert!(shown.starts_with("########\n"), "{}", shown);
+
+    let output = run_solver(&["deadlocks", "--clear", db, &map_path("8boxes1")]);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "cleared: 0\n");
+    let output = run_solver(&["deadlocks", "--clear", db]);
+    assert!(output.status.success());
+    let listing = String::from_utf8(run_solver(&["deadlocks", db]).stdout).unwrap();
+    assert_eq!(listing, "levels: 0\ndeadlocks: 0\n");
+
+    let output = run_solver(&["deadlocks", db, &map, "--level"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8(output.stderr).unwrap().contains("--level needs a value"));
+
+    fs::remove_file(db).unwrap();
+}
+
+#[test]
+fn puzzles_are_read_from_stdin() {
+    let map = fs::read_to_string(map_path("twoboxes1")).unwrap();
+    let output = run_solver_with_stdin(&["--objective", "pushes", "-"], &map);
+    assert!(output.status.success());
+    let expected = run_solver(&["--objective", "pushes", &map_path("twoboxes1")]);
+    assert_eq!(output.stdout, expected.stdout);
+
+    let collection = "; first\n#####\n#@$.#\n#####\n\nSecond\n######\n#@$ .#\n######\n";
+    let output = run_solver_with_stdin(&["--level", "second", "-"], collection);
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "RR");
+
+    let output = run_solver_with_stdin(&["export", "--rle", "-"], "#####\n#@$.#\n#####\n");
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "5#|#@$.#|5#");
+
+    let output = run_solver_with_stdin(&["-"], "#####\n#@$x#\n#####\n");
+    assert_eq!(output.status.code(), Some(3));
+}
+
+fn request(fields: Value) -> String {
+    format!("{}\n", fields)
+}
+
+#[test]
+fn serve_answers_json_lines_requests() {
+    let map = fs::read_to_string(map_path("twoboxes1")).unwrap();
+    let requests = [
+        request(serde_json::json!({"id": 1, "type": "solve", "puzzle": map, "options": {"objective": "pushes"}})),
+        request(serde_json::json!({"id": 2, "type": "solve", "puzzle": map, "lowercase": true})),
+        request(serde_json::json!({"id": 3, "type": "verify", "puzzle": "#####\n#@$.#\n#####", "solution": "Ru"})),
+        request(serde_json::json!({"id": 4, "type": "solve", "puzzle": "#####\n#@$x#\n#####"})),
+        request(serde_json::json!({"id": 5, "type": "solve", "puzzle": map, "options": {"objective": "fewest"}})),
+        "not json\n".to_string(),
+    ];
+    let output = run_solver_with_stdin(&["serve"], &requests.concat());
+    assert!(output.status.success());
+    let responses: Vec<Value> =
+        String::from_utf8_lossy(&output.stdout).lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+    let response = |id: Value, event: &str| {
+        let found = responses.iter().find(|r| r["id"] == id && r["event"] == event);
+        found.unwrap_or_else(|| panic!("no {} for {}", event, id))
+    };
+
+    let first = response(1.into(), "result");
+    assert_eq!(first["termination"], "solved");
+    assert_eq!(first["pushes"], 9);
+    assert_eq!(first["config"]["objective"], "pushes");
+    let second = response(2.into(), "result");
+    assert!(second["solution"].as_str().unwrap().chars().all(|c| c.is_ascii_lowercase()));
+
+    let verified = response(3.into(), "verify");
+    assert_eq!(verified["solved"], false);
+    assert_eq!(verified["illegal"]["index"], 1);
+    assert_eq!(response(4.into(), "result")["termination"], "parse");
+    assert!(response(5.into(), "error")["error"].as_str().unwrap().contains("unknown objective"));
+    assert!(response(Value::Null, "error")["error"].as_str().unwrap().starts_with("invalid JSON"));
+}
+
+#[test]
+fn serve_streams_progress_and_cancels_a_running_solve() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rust_solver"))
+        .arg("serve")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    let mut lines = BufReader::new(child.stdout.take().unwrap()).lines();
+    let mut next = || -> Value { serde_json::from_str(&lines.next().unwrap().unwrap()).unwrap() };
+
+    let map = fs::read_to_string(map_path("original2")).unwrap();
+    let solve = serde_json::json!({"id": "hard", "type": "solve", "puzzle": map, "progress_ms": 20});
+    stdin.write_all(request(solve).as_bytes()).unwrap();
+    let progress = next();
+    assert_eq!(progress["event"], "progress");
+    assert_eq!(progress["id"], "hard");
+    assert!(progress["nodes_expanded"].as_u64().is_some());
+
+    stdin.write_all(request(serde_json::json!({"id": "s", "type": "stats"})).as_bytes()).unwrap();
+    stdin.write_all(request(serde_json::json!({"id": "c", "type": "cancel"})).as_bytes()).unwrap();
+    drop(stdin);
+    let mut responses = Vec::new();
+    loop {
+        let response = next();
+        let done = response["event"] == "result";
+        responses.push(response);
+        if done {
+            break;
+        }
+    }
+    assert!(child.wait().unwrap().success());
+
+    let stats = responses.iter().find(|r| r["event"] == "stats").unwrap();
+    assert_eq!(stats["running"], "hard");
+    let cancel = responses.iter().find(|r| r["event"] == "cancel").unwrap();
+    assert_eq!(cancel["cancelled"], 1);
+    let result = responses.last().unwrap();
+    assert_eq!(result["id"], "hard");
+    assert_eq!(result["termination"], "cancelled");
+}
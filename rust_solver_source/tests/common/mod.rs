@@ -0,0 +1,39 @@
+#![allow(dead_code)]
+
+use rust_solver::{Point, Puzzle};
+
+pub fn load_map(name: &str) -> Puzzle {
+    let path = format!("{}/../maps/{}.txt", env!("CARGO_MANIFEST_DIR"), name);
+    Puzzle::load(&path).unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// Replays `moves` on `puzzle` and returns true if every step is legal and
+/// all boxes end on goals.
+pub fn replays_to_solved(puzzle: &Puzzle, moves: &str) -> bool {
+    let mut player = puzzle.player();
+    let mut boxes: Vec<Point> = puzzle.boxes().to_vec();
+
+    for ch in moves.chars() {
+        let (dr, dc) = match ch.to_ascii_lowercase() {
+            'u' => (-1, 0),
+            'd' => (1, 0),
+            'l' => (0, -1),
+            'r' => (0, 1),
+            _ => return false,
+        };
+        let next = Point::new(player.row + dr, player.col + dc);
+        if puzzle.is_wall(next) {
+            return false;
+        }
+        if let Some(i) = boxes.iter().position(|&b| b == next) {
+            let beyond = Point::new(next.row + dr, next.col + dc);
+            if puzzle.is_wall(beyond) || boxes.contains(&beyond) {
+                return false;
+            }
+            boxes[i] = beyond;
+        }
+        player = next;
+    }
+
+    boxes.iter().all(|&b| puzzle.is_goal(b))
+}
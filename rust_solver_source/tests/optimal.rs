@@ -0,0 +1,75 @@
+mod common;
+
+use common::{load_map, replays_to_solved};
+use rust_solver::{solve, Heuristic, Objective, Puzzle, Solution, SolveOptions};
+
+// (map, moves, pushes) of the move-optimal and push-optimal solutions,
+// from an exhaustive uniform-cost search over player moves.
+const KNOWN_OPTIMAL: [(&str, usize, usize); 8] = [
+    ("twoboxes1", 29, 9),
+    ("twoboxes2", 48, 18),
+    ("twoboxes3", 50, 18),
+    ("threeboxes1", 70, 22),
+    ("threeboxes2", 133, 51),
+    ("threeboxes3", 83, 18),
+    ("fourboxes1", 81, 24),
+    ("testlevel", 37, 7),
+];
+
+// Moves-optimal is 15 moves / 6 pushes; pushes-optimal is 23 moves / 4 pushes.
+const TRADE_OFF: &str = "\
+#######
+#     #
+#@##$ #
+# $.  #
+#.    #
+#######";
+
+fn solve_with(puzzle: &Puzzle, objective: Objective) -> Solution {
+    solve_with_heuristic(puzzle, objective, Heuristic::default())
+}
+
+fn solve_with_heuristic(puzzle: &Puzzle, objective: Objective, heuristic: Heuristic) -> Solution {
+    let options = SolveOptions { objective, heuristic, ..Default::default() };
+    let solution = solve(puzzle, &options).unwrap();
+    assert!(replays_to_solved(puzzle, &solution.moves), "{:?}: {}", objective, solution.moves);
+    solution
+}
+
+#[test]
+fn finds_known_optimal_solutions_for_bundled_maps() {
+    for (name, moves, pushes) in KNOWN_OPTIMAL {
+        let puzzle = load_map(name);
+        assert_eq!(solve_with(&puzzle, Objective::Moves).moves.len(), moves, "{}", name);
+        assert_eq!(solve_with(&puzzle, Objective::Pushes).pushes, pushes, "{}", name);
+
+        let s = solve_with(&puzzle, Objective::MovesThenPushes);
+        assert_eq!((s.moves.len(), s.pushes), (moves, pushes), "{}", name);
+        let s = solve_with(&puzzle, Objective::PushesThenMoves);
+        assert_eq!((s.moves.len(), s.pushes), (moves, pushes), "{}", name);
+    }
+}
+
+#[test]
+fn objectives_trade_moves_against_pushes() {
+    let puzzle = Puzzle::parse(TRADE_OFF).unwrap();
+
+    assert_eq!(solve_with(&puzzle, Objective::Moves).moves.len(), 15);
+    assert_eq!(solve_with(&puzzle, Objective::Pushes).pushes, 4);
+
+    let s = solve_with(&puzzle, Objective::MovesThenPushes);
+    assert_eq!((s.moves.len(), s.pushes), (15, 6));
+    let s = solve_with(&puzzle, Objective::PushesThenMoves);
+    assert_eq!((s.moves.len(), s.pushes), (23, 4));
+}
+
+#[test]
+fn greedy_heuristic_lower_bound_is_also_optimal() {
+    for (name, moves, pushes) in KNOWN_OPTIMAL {
+        let puzzle = load_map(name);
+        let s = solve_with_heuristic(&puzzle, Objective::MovesThenPushes, Heuristic::Greedy);
+        assert_eq!((s.moves.len(), s.pushes), (moves, pushes), "{}", name);
+        let s = solve_with_heuristic(&puzzle, Objective::Pushes, Heuristic::Greedy);
+        assert_eq!(s.pushes, pushes, "{}", name);
+    }
+}
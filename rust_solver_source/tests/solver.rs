@@ -0,0 +1,524 @@
+mod common;
+
+use common::{load_map, replays_to_solved};
+use rust_solver::{
+    solve, solve_str, to_lurd, verify, ClosedSetMode, Collection, DeadlockDb, Diagnostic,
+    Direction, Heuristic, IllegalReason, LearnedDeadlock, Move, Objective, Patterns, Point,
+    Progress, Puzzle, SolveError, SolveOptions, Solver,
+};
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[test]
+fn parse_reads_player_boxes_and_goals() {
+    let puzzle = Puzzle::parse("#####\n#+$ #\n# *$#\n#  .#\n#####").unwrap();
+
+    assert_eq!(puzzle.width(), 5);
+    assert_eq!(puzzle.height(), 5);
+    assert_eq!(puzzle.player(), Point::new(1, 1));
+    assert_eq!(puzzle.boxes(), &[Point::new(1, 2), Point::new(2, 2), Point::new(2, 3)]);
+    assert_eq!(puzzle.goals(), &[Point::new(1, 1), Point::new(2, 2), Point::new(3, 3)]);
+    assert!(puzzle.is_wall(Point::new(0, 0)));
+    assert!(puzzle.is_wall(Point::new(-1, 2)));
+    assert!(!puzzle.is_wall(Point::new(1, 3)));
+}
+
+#[test]
+fn solves_bundled_maps() {
+    for name in ["twoboxes1", "twoboxes2", "threeboxes1", "fourboxes1", "testlevel"] {
+        let puzzle = load_map(name);
+        let solution = solve(&puzzle, &SolveOptions::default()).unwrap();
+        assert!(replays_to_solved(&puzzle, &solution.moves), "{}: {}", name, solution.moves);
+    }
+}
+
+#[test]
+fn greedy_heuristic_still_solves() {
+    let options = SolveOptions { heuristic: Heuristic::Greedy, ..Default::default() };
+    for name in ["twoboxes1", "threeboxes2", "fourboxes2"] {
+        let puzzle = load_map(name);
+        let solution = solve(&puzzle, &options).unwrap();
+        assert!(replays_to_solved(&puzzle, &solution.moves), "{}", name);
+    }
+}
+
+#[test]
+fn solutions_use_lurd_with_uppercase_pushes() {
+    let puzzle = load_map("threeboxes1");
+    let solution = solve(&puzzle, &SolveOptions::default()).unwrap();
+    let uppercase = solution.moves.chars().filter(|c| c.is_ascii_uppercase()).count();
+    assert_eq!(uppercase, solution.pushes);
+
+    let steps: Vec<Move> = solution.moves.chars().map(|c| Move::from_char(c).unwrap()).collect();
+    assert_eq!(steps.iter().filter(|m| m.push).count(), solution.pushes);
+    assert_eq!(to_lurd(&steps), solution.moves);
+    assert_eq!(Move::from_char('L'), Some(Move::push(Direction::Left)));
+    assert_eq!(Move::from_char('x'), None);
+}
+
+#[test]
+fn solver_can_be_reused() {
+    let puzzle = load_map("twoboxes1");
+    let mut solver = Solver::new(&puzzle);
+    let first = solver.solve(&SolveOptions::default()).unwrap();
+    let second = solver.solve(&SolveOptions::default()).unwrap();
+    assert!(replays_to_solved(&puzzle, &first.moves));
+    assert!(replays_to_solved(&puzzle, &second.moves));
+}
+
+#[test]
+fn already_solved_puzzle_needs_no_moves() {
+    let puzzle = Puzzle::parse("#####\n#@* #\n#####").unwrap();
+    assert_eq!(solve(&puzzle, &SolveOptions::default()).unwrap().moves, "");
+}
+
+#[test]
+fn unsolvable_puzzle_reports_error() {
+    let puzzle = Puzzle::parse("#####\n#@ $#\n#.  #\n#####").unwrap();
+    assert_eq!(solve(&puzzle, &SolveOptions::default()), Err(SolveError::Unsolvable));
+}
+
+#[test]
+fn frozen_boxes_prune_only_off_goal() {
+    // A 2x2 block of boxes can never move again.
+    let blocked = Puzzle::parse("########\n#      #\n# $$   #\n# $$ @ #\n#  ....#\n########").unwrap();
+    let mut solver = Solver::new(&blocked);
+    assert_eq!(solver.solve(&SolveOptions::default()), Err(SolveError::Unsolvable));
+    assert_eq!(solver.stats().nodes_expanded, 0);
+
+    // Completing a block on goals is still allowed.
+    let on_goals = Puzzle::parse("#######\n#     #\n# **  #\n# *.$@#\n#     #\n#######").unwrap();
+    assert_eq!(solve(&on_goals, &SolveOptions::default()).unwrap().moves, "L");
+
+    // Pushing the top box onto its corner goal freezes the box below it off
+    // a goal, and nothing else can move it; without patterns only the
+    // freeze check can catch that.
+    let neighbour = Puzzle::parse("#######\n#.$  .#\n#$    #\n#  @  #\n#######").unwrap();
+    let options = SolveOptions { patterns: Arc::new(Patterns::none()), ..SolveOptions::default() };
+    let mut solver = Solver::new(&neighbour);
+    assert_eq!(solver.solve(&options), Err(SolveError::Unsolvable));
+    assert!(solver.stats().freeze_deadlocks > 0);
+
+    let puzzle = load_map("fourboxes1");
+    let solution = solve(&puzzle, &SolveOptions::default()).unwrap();
+    assert!(solution.stats.freeze_deadlocks > 0);
+    assert!(replays_to_solved(&puzzle, &solution.moves));
+}
+
+#[test]
+fn patterns_prune_matching_pushes() {
+    // Pushing the lower left box up closes the corner off behind the top
+    // box: neither can move again, yet neither is frozen.
+    let puzzle = Puzzle::parse("#######\n# *   #\n# #   #\n#*    #\n#@ $ .#\n#######").unwrap();
+    let solution = solve(&puzzle, &SolveOptions::default()).unwrap();
+    assert!(solution.stats.pattern_deadlocks > 0);
+    assert!(replays_to_solved(&puzzle, &solution.moves));
+
+    let options = SolveOptions { patterns: Arc::new(Patterns::none()), ..SolveOptions::default() };
+    let solution = solve(&puzzle, &options).unwrap();
+    assert_eq!(solution.stats.pattern_deadlocks, 0);
+    assert!(replays_to_solved(&puzzle, &solution.moves));
+
+    // The same with a third box instead of the wall diagonal to the corner.
+    let boxed = Puzzle::parse("#######\n# *   #\n# *   #\n#*    #\n#@ $ .#\n#######").unwrap();
+    let solution = solve(&boxed, &SolveOptions::default()).unwrap();
+    assert!(solution.stats.pattern_deadlocks > 0);
+    assert!(replays_to_solved(&boxed, &solution.moves));
+}
+
+#[test]
+fn pattern_libraries_parse_with_positions_in_errors() {
+    let patterns = Patterns::parse("test", "; two patterns\nxx?\nxx?\n???\n\n#$?\n?$#\n???\n").unwrap();
+    assert_eq!(patterns.name(), "test");
+    assert_eq!(patterns.len(), 2);
+    assert!(!Patterns::builtin().is_empty());
+    assert!(Patterns::none().is_empty());
+
+    assert_eq!(
+        Patterns::parse("test", "xx?\nxq?\n???").unwrap_err(),
+        SolveError::Parse { line: 2, column: 2, message: "unexpected pattern cell 'q'".to_string() }
+    );
+    assert!(matches!(
+        Patterns::parse("test", "; short\nxx?\nxx?\n").unwrap_err(),
+        SolveError::Parse { line: 2, column: 1, .. }
+    ));
+}
+
+#[test]
+fn matching_deadlocks_prune_boxes_sharing_one_goal() {
+    // Boxes against the top wall can only reach the goal in that row.
+    let shared = Puzzle::parse("#########\n# $ . $ #\n#       #\n#   .  @#\n#########").unwrap();
+    let mut solver = Solver::new(&shared);
+    assert_eq!(solver.solve(&SolveOptions::default()), Err(SolveError::Unsolvable));
+    assert_eq!(solver.stats().nodes_expanded, 0);
+
+    // Pushing the lower box up to the wall would leave the same situation.
+    let puzzle = Puzzle::parse("#########\n# $ .   #\n#     $ #\n#   .  @#\n#########").unwrap();
+    let solution = solve(&puzzle, &SolveOptions::default()).unwrap();
+    assert!(solution.stats.matching_deadlocks > 0);
+    assert!(replays_to_solved(&puzzle, &solution.moves));
+}
+
+#[test]
+fn corral_deadlocks_prune_fenced_off_areas() {
+    // Pushing the first box in only jams it against the second; neither can
+    // ever be pushed back left. The box left of the player is not part of it.
+    let fenced = Puzzle::parse("##########\n#*@.$ $ .#\n##########").unwrap();
+    let mut solver = Solver::new(&fenced);
+    assert_eq!(solver.solve(&SolveOptions::default()), Err(SolveError::Unsolvable));
+    assert_eq!(solver.stats().corral_deadlocks, 1);
+    assert_eq!(solver.stats().nodes_expanded, 0);
+
+    let puzzle = load_map("fiveboxes3");
+    let solution = solve(&puzzle, &SolveOptions::default()).unwrap();
+    assert!(solution.stats.corral_deadlocks > 0);
+    assert!(replays_to_solved(&puzzle, &solution.moves));
+}
+
+#[test]
+fn learned_deadlocks_carry_over_to_later_runs() {
+    let puzzle = load_map("8boxes5");
+    let mut first = Solver::new(&puzzle);
+    let cold = first.solve(&SolveOptions::default()).unwrap();
+    assert!(cold.stats.learned_deadlocks > 0);
+    assert!(!first.learned().is_empty());
+
+    let mut db = DeadlockDb::new();
+    assert_eq!(db.record(&puzzle, first.learned()), first.learned().len());
+    assert_eq!(db.record(&puzzle, first.learned()), 0);
+    let db = DeadlockDb::parse(&db.to_text()).unwrap();
+    assert_eq!(db.deadlocks(&puzzle), first.learned());
+
+    let mut second = Solver::new(&puzzle);
+    second.learn(db.deadlocks(&puzzle));
+    let warm = second.solve(&SolveOptions::default()).unwrap();
+    assert!(warm.stats.nodes_expanded < cold.stats.nodes_expanded);
+    assert!(replays_to_solved(&puzzle, &warm.moves));
+
+    // Deadlocks belong to the walls and goals, not to the start position.
+    let moved = Puzzle::parse(&puzzle.to_text().replacen('@', " ", 1).replacen("  ##", "@ ##", 1)).unwrap();
+    assert_ne!(moved.player(), puzzle.player());
+    assert_eq!(moved.layout_hash(), puzzle.layout_hash());
+    assert_ne!(load_map("8boxes1").layout_hash(), puzzle.layout_hash());
+
+    assert_eq!(
+        DeadlockDb::parse("; header\n69ce2fda4a95c575 1,5 3;3").unwrap_err(),
+        SolveError::Parse { line: 2, column: 22, message: "invalid square '3;3'".to_string() }
+    );
+    // Hand-edited lines may have runs of spaces or trailing blanks.
+    let spaced = DeadlockDb::parse(&format!("{:016x}  1,5   3,3 \n", puzzle.layout_hash())).unwrap();
+    assert_eq!(spaced.deadlocks(&puzzle), [LearnedDeadlock { player: Point::new(1, 5), boxes: vec![Point::new(3, 3)] }]);
+    assert_eq!(
+        DeadlockDb::parse("69ce2fda4a95c575  1,5   3;3").unwrap_err(),
+        SolveError::Parse { line: 1, column: 25, message: "invalid square '3;3'".to_string() }
+    );
+}
+
+#[test]
+fn parse_reports_position_of_unknown_character() {
+    let err = Puzzle::parse("#####\n#@$.#\n#  x#\n#####").unwrap_err();
+    assert_eq!(
+        err,
+        SolveError::Parse {
+            line: 3,
+            column: 4,
+            message: "unexpected character 'x'".to_string(),
+        }
+    );
+}
+
+#[test]
+fn parse_accepts_rle_row_separators_and_floor_aliases() {
+    let plain = Puzzle::parse("#######\n#@ $ .#\n#######").unwrap();
+    for text in ["7#|#@-$-.#|7#", "7#|#@_$_.#|7#\n", "7#\n#@-$-.#\n3#4#", "#######\n#@-$_.#\n#######"] {
+        let puzzle = Puzzle::parse(text).unwrap_or_else(|e| panic!("{}: {}", text, e));
+        assert_eq!((puzzle.width(), puzzle.height()), (7, 3), "{}", text);
+        assert_eq!(puzzle.player(), plain.player());
+        assert_eq!(puzzle.boxes(), plain.boxes());
+        assert_eq!(puzzle.goals(), plain.goals());
+    }
+
+    let err = Puzzle::parse("7#|#@-$-.#|7").unwrap_err();
+    assert!(matches!(err, SolveError::Parse { line: 1, column: 12, .. }), "{:?}", err);
+
+    // A zero count would silently drop the cell and reshape the row.
+    let err = Puzzle::parse("7#|#@3-0$.#|7#").unwrap_err();
+    assert!(matches!(err, SolveError::Parse { line: 1, column: 8, .. }), "{:?}", err);
+    let err = Puzzle::parse("0#7#|#@-$-.#|7#").unwrap_err();
+    assert!(matches!(err, SolveError::Parse { line: 1, column: 1, .. }), "{:?}", err);
+}
+
+#[test]
+fn puzzles_export_as_plain_text_and_rle() {
+    for name in ["twoboxes1", "fourboxes1", "original1"] {
+        let puzzle = load_map(name);
+        for text in [puzzle.to_text(), puzzle.to_rle()] {
+            let again = Puzzle::parse(&text).unwrap();
+            assert_eq!(again.to_text(), puzzle.to_text(), "{}", name);
+            assert_eq!(again.player(), puzzle.player());
+            assert_eq!(again.boxes(), puzzle.boxes());
+        }
+    }
+
+    let puzzle = Puzzle::parse("######\n#+$  #\n#  *$#\n#   .#\n######").unwrap();
+    assert_eq!(puzzle.to_text(), "######\n#+$  #\n#  *$#\n#   .#\n######\n");
+    assert_eq!(puzzle.to_rle(), "6#|#+$2-#|#2-*$#|#3-.#|6#");
+}
+
+#[test]
+fn solve_str_solves_text_held_in_memory() {
+    let options = SolveOptions::default();
+    let solution = solve_str("#####\n#@$.#\n#####\n", &options).unwrap();
+    assert_eq!(solution.moves, "R");
+    assert_eq!(solve_str("5#|#@$.#|5#", &options).unwrap().moves, "R");
+    assert!(matches!(solve_str("#####\n#@$x#\n#####", &options), Err(SolveError::Parse { .. })));
+}
+
+#[test]
+fn parse_rejects_missing_player() {
+    let err = Puzzle::parse("#####\n# $.#\n#####").unwrap_err();
+    assert_eq!(err, SolveError::InvalidPuzzle(vec![Diagnostic::NoPlayer]));
+}
+
+#[test]
+fn validation_reports_every_problem() {
+    let err = Puzzle::parse("######\n#@$ @#\n#..$ #\n######\n  $   ").unwrap_err();
+    assert_eq!(
+        err,
+        SolveError::InvalidPuzzle(vec![
+            Diagnostic::ExtraPlayer(Point::new(1, 4)),
+            Diagnostic::CountMismatch { boxes: 3, goals: 2 },
+            Diagnostic::UnreachableBox(Point::new(4, 2)),
+        ])
+    );
+}
+
+#[test]
+fn validation_detects_open_boundary() {
+    let err = Puzzle::parse("#####\n#@$.\n#####").unwrap_err();
+    assert_eq!(err, SolveError::InvalidPuzzle(vec![Diagnostic::OpenBoundary(Point::new(1, 4))]));
+}
+
+#[test]
+fn validation_limits_goal_count() {
+    let row = format!("#{}#", "*".repeat(65));
+    let wall = "#".repeat(67);
+    let text = format!("{}\n{}\n#@{}#\n{}", wall, row, " ".repeat(64), wall);
+    let err = Puzzle::parse(&text).unwrap_err();
+    assert_eq!(err, SolveError::InvalidPuzzle(vec![Diagnostic::TooManyGoals(65)]));
+}
+
+#[test]
+fn bundled_maps_are_valid() {
+    let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/../maps");
+    for entry in std::fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if let Err(err) = Puzzle::load(&path) {
+            panic!("{}: {}", path.display(), err);
+        }
+    }
+}
+
+#[test]
+fn load_reports_io_errors() {
+    let err = Puzzle::load("/nonexistent/level.txt").unwrap_err();
+    assert!(matches!(err, SolveError::Io { .. }));
+}
+
+#[test]
+fn transposition_table_counts_hits_and_misses() {
+    let puzzle = load_map("fourboxes2");
+
+    let mut solver = Solver::new(&puzzle);
+    let solution = solver.solve(&SolveOptions::default()).unwrap();
+    assert!(solution.stats.tt_hits > 0);
+    assert!(solution.stats.tt_misses > 0);
+    assert_eq!(solver.stats(), &solution.stats);
+
+    let disabled = SolveOptions { tt_size: 0, ..Default::default() };
+    let uncached = solver.solve(&disabled).unwrap();
+    assert_eq!(uncached.stats.tt_hits, 0);
+    assert_eq!(uncached.moves, solution.moves);
+}
+
+#[test]
+fn stats_describe_the_search() {
+    let puzzle = load_map("fourboxes1");
+    let solution = solve(&puzzle, &SolveOptions::default()).unwrap();
+    let stats = &solution.stats;
+
+    assert!(stats.start_heuristic > 0);
+    assert!(stats.nodes_generated >= stats.nodes_expanded);
+    // Positions pruned as corral or learned deadlocks are closed without
+    // expanding.
+    assert_eq!(stats.closed_set_size, stats.nodes_expanded + stats.corral_deadlocks + stats.learned_deadlocks);
+    assert!(stats.peak_open_set > 0 && stats.peak_open_set <= stats.nodes_generated);
+}
+
+#[test]
+fn same_seed_gives_identical_runs() {
+    let puzzle = load_map("fourboxes3");
+    let options = SolveOptions::default();
+    let first = Solver::new(&puzzle).solve(&options).unwrap();
+    let second = Solver::new(&puzzle).solve(&options).unwrap();
+    assert_eq!(first, second);
+
+    let reseeded = SolveOptions { seed: 7, ..Default::default() };
+    let other = Solver::new(&puzzle).solve(&reseeded).unwrap();
+    assert!(replays_to_solved(&puzzle, &other.moves));
+}
+
+#[test]
+fn closed_set_modes_agree() {
+    let puzzle = load_map("fourboxes2");
+    let exact = solve(&puzzle, &SolveOptions::default()).unwrap();
+    let hashed = SolveOptions { closed_set: ClosedSetMode::HashOnly, ..Default::default() };
+    let hashed = solve(&puzzle, &hashed).unwrap();
+
+    assert_eq!(exact.moves, hashed.moves);
+    assert_eq!(exact.stats.hash_collisions, 0);
+    assert_eq!(hashed.stats.hash_collisions, 0);
+}
+
+#[test]
+fn solves_long_push_sequences() {
+    let corridor = format!("{}\n#@${}.#\n{}", "#".repeat(304), " ".repeat(298), "#".repeat(304));
+    let puzzle = Puzzle::parse(&corridor).unwrap();
+    let solution = solve(&puzzle, &SolveOptions::default()).unwrap();
+    assert_eq!(solution.pushes, 299);
+    assert_eq!(solution.moves, "R".repeat(299));
+}
+
+#[test]
+fn limits_stop_the_search_with_partial_progress() {
+    let puzzle = load_map("fiveboxes3");
+
+    let options = SolveOptions { max_nodes: Some(50), ..Default::default() };
+    let err = solve(&puzzle, &options).unwrap_err();
+    assert!(matches!(err, SolveError::NodeLimit(_)), "{:?}", err);
+    let partial = err.partial().unwrap();
+    assert_eq!(partial.stats.nodes_expanded, 50);
+    assert_eq!(partial.moves.chars().filter(|c| "udlrUDLR".contains(*c)).count(), partial.moves.len());
+
+    let options = SolveOptions { time_limit: Some(Duration::ZERO), ..Default::default() };
+    assert!(matches!(solve(&puzzle, &options), Err(SolveError::Timeout(_))));
+
+    let options = SolveOptions { max_memory: Some(1), ..Default::default() };
+    assert!(matches!(solve(&puzzle, &options), Err(SolveError::OutOfMemory(_))));
+}
+
+#[test]
+fn portfolio_solves_and_agrees_on_unsolvable() {
+    let options = SolveOptions { threads: 4, ..Default::default() };
+    for name in ["twoboxes2", "fourboxes1", "fiveboxes2"] {
+        let puzzle = load_map(name);
+        let solution = solve(&puzzle, &options).unwrap();
+        assert!(replays_to_solved(&puzzle, &solution.moves), "{}: {}", name, solution.moves);
+    }
+
+    let puzzle = Puzzle::parse("#####\n#@ $#\n#.  #\n#####").unwrap();
+    assert_eq!(solve(&puzzle, &options), Err(SolveError::Unsolvable));
+}
+
+#[test]
+fn portfolio_keeps_optimal_objectives_optimal() {
+    let puzzle = load_map("threeboxes1");
+    let single = SolveOptions { objective: Objective::Pushes, ..Default::default() };
+    let parallel = SolveOptions { threads: 2, ..single.clone() };
+    assert_eq!(solve(&puzzle, &parallel).unwrap().pushes, solve(&puzzle, &single).unwrap().pushes);
+}
+
+#[test]
+fn cancel_flag_stops_the_search() {
+    let puzzle = load_map("fiveboxes3");
+    let cancel = Arc::new(AtomicBool::new(true));
+    for threads in [1, 3] {
+        let options = SolveOptions { threads, cancel: Some(cancel.clone()), ..Default::default() };
+        assert_eq!(solve(&puzzle, &options), Err(SolveError::Cancelled));
+    }
+}
+
+#[test]
+fn progress_follows_a_running_search() {
+    let puzzle = load_map("8boxes1");
+    let progress = Arc::new(Progress::default());
+    assert_eq!(progress.best_heuristic(), None);
+    let max_nodes = 5000;
+    let options = SolveOptions { max_nodes: Some(max_nodes), progress: Some(progress.clone()), ..Default::default() };
+    let err = solve(&puzzle, &options).unwrap_err();
+    let stats = &err.partial().unwrap().stats;
+
+    // Counters are published every 1024 expansions.
+    let expanded = progress.nodes_expanded();
+    assert!(expanded > 0 && expanded % 1024 == 0 && expanded <= max_nodes, "{}", expanded);
+    assert!(progress.nodes_generated() <= stats.nodes_generated);
+    assert!(progress.best_heuristic().unwrap() <= stats.start_heuristic);
+}
+
+#[test]
+fn verify_replays_solutions() {
+    let puzzle = load_map("threeboxes2");
+    let solution = solve(&puzzle, &SolveOptions::default()).unwrap();
+    let replay = verify(&puzzle, &solution.moves).unwrap();
+    assert!(replay.solved);
+    assert_eq!(replay.moves, solution.moves.len());
+    assert_eq!(replay.pushes, solution.pushes);
+
+    let lowercase = verify(&puzzle, &solution.moves.to_ascii_lowercase()).unwrap();
+    assert_eq!(lowercase, replay);
+
+    let half = &solution.moves[..solution.moves.len() / 2];
+    assert!(!verify(&puzzle, half).unwrap().solved);
+}
+
+#[test]
+fn verify_reports_the_first_illegal_move() {
+    let puzzle = Puzzle::parse("#######\n#@$ $.#\n#    .#\n#######").unwrap();
+
+    let wall = verify(&puzzle, "rdu u").unwrap_err();
+    assert_eq!((wall.index, wall.position, wall.reason), (3, Point::new(1, 2), IllegalReason::Wall));
+    assert_eq!(wall.pushes, 1);
+
+    let blocked = verify(&puzzle, "RR").unwrap_err();
+    assert_eq!((blocked.index, blocked.reason), (1, IllegalReason::BoxBlocked));
+    assert_eq!(verify(&puzzle, "D").unwrap_err().reason, IllegalReason::NotAPush);
+    assert_eq!(verify(&puzzle, "dR").unwrap_err().reason, IllegalReason::NotAPush);
+    assert_eq!(verify(&puzzle, "RdU").unwrap_err().reason, IllegalReason::NotAPush);
+    assert_eq!(verify(&puzzle, "rR").unwrap_err().reason, IllegalReason::UnmarkedPush);
+    assert_eq!(verify(&puzzle, "x").unwrap_err().reason, IllegalReason::UnknownCharacter('x'));
+}
+
+#[test]
+fn collections_split_levels_and_keep_metadata() {
+    let text = "Title: Tiny set\r\nAuthor: Someone\r\n\r\n; first\r\n\r\n#####\r\n#@$.#\r\n#####\r\nTitle: Corridor\r\n\r\n\
+                Level B\n; a comment\n#####\n#.$@#\n#####\nAuthor: Other\n";
+    let collection = Collection::parse(text);
+
+    assert_eq!(collection.title.as_deref(), Some("Tiny set"));
+    assert_eq!(collection.author.as_deref(), Some("Someone"));
+    assert_eq!(collection.levels.len(), 2);
+
+    let first = &collection.levels[0];
+    assert_eq!(first.text, "#####\n#@$.#\n#####\n");
+    assert_eq!(first.line, 6);
+    assert_eq!(first.title.as_deref(), Some("Corridor"));
+    assert_eq!(first.comments, ["first"]);
+
+    let second = &collection.levels[1];
+    assert_eq!(second.title.as_deref(), Some("Level B"));
+    assert_eq!(second.author.as_deref(), Some("Other"));
+    assert_eq!(second.comments, ["a comment"]);
+    assert!(second.puzzle().is_ok());
+
+    assert_eq!(collection.select("2").unwrap().0, 1);
+    assert_eq!(collection.select("corridor").unwrap().0, 0);
+    assert_eq!(
+        collection.select("9").unwrap_err(),
+        SolveError::NoSuchLevel { selector: "9".to_string(), levels: 2 }
+    );
+
+    let plain = Collection::load("../maps/twoboxes1.txt").unwrap();
+    assert_eq!(plain.levels.len(), 1);
+    assert_eq!(plain.levels[0].puzzle().unwrap().boxes(), load_map("twoboxes1").boxes());
+}
@@ -27,4 +27,13 @@ public class BotThread extends Thread {
   public String getSolution() {
     return solution;
   }
+
+  // Stops the solver's search; the thread then ends without a solution
+  public void cancel() {
+    sokoBot.cancel();
+  }
+
+  public boolean wasCancelled() {
+    return sokoBot.wasCancelled();
+  }
 }
@@ -49,6 +49,7 @@ public class GamePanel extends JPanel implements KeyListener, ActionListener {
   private final String STATUS_WAITING_FOR_SPACE = "Push SPACE to start Bot...";
   private final String STATUS_WAITING_FOR_SOLUTION = "Waiting for solution...";
   private final String STATUS_SOLUTION_TIMEOUT = "TIME'S UP! Bot took too long thinking...";
+  private final String STATUS_SOLUTION_CANCELLED = "Bot stopped. Key pressed while thinking.";
   private final String STATUS_PLAYING_SOLUTION = "Playing solution...";
   private final String STATUS_FINISHED_PLAYING_SOLUTION = "SOLUTION FINISHED!";
   private final String STATUS_FREE_PLAY = "FREE PLAY MODE!";
@@ -332,6 +333,9 @@ public class GamePanel extends JPanel implements KeyListener, ActionListener {
 
         this.repaint();
       }
+    } else if (solutionThread != null && solutionThread.isAlive()) {
+      // Any key stops the search; checkForSolutionTimer reports it
+      solutionThread.cancel();
     }
   }
 
@@ -367,7 +371,8 @@ public class GamePanel extends JPanel implements KeyListener, ActionListener {
         this.repaint();
         return;
       }
-      int nextMove = this.solutionString.charAt(this.solutionCtr++);
+      // LURD: uppercase letters mark pushes, which move the same way.
+      int nextMove = Character.toLowerCase(this.solutionString.charAt(this.solutionCtr++));
       switch (nextMove) {
         case 'u':
           executeMove(0);
@@ -384,19 +389,24 @@ public class GamePanel extends JPanel implements KeyListener, ActionListener {
       }
     } else if (e.getSource() == checkForSolutionTimer) {
       if (!solutionThread.isAlive()) {
-        // Solution was found
         solutionTimer.stop();
         checkForSolutionTimer.stop();
-        String solution = solutionThread.getSolution();
-        this.playSolution(solution);
+        if (solutionThread.wasCancelled()) {
+          this.statusString = STATUS_SOLUTION_CANCELLED;
+        } else {
+          // Solution was found
+          String solution = solutionThread.getSolution();
+          this.playSolution(solution);
+        }
       }
       long elapsedSolutionTime = System.nanoTime() - solutionStartTime;
       this.solutionTimeString = String.format("%.2f", elapsedSolutionTime / 1000000000.0) + "s";
       this.repaint();
     } else if (e.getSource() == solutionTimer) {
-      // Solution was not found
+      // Solution was not found; free the solver for the next map
       solutionTimer.stop();
       checkForSolutionTimer.stop();
+      solutionThread.cancel();
       long elapsedSolutionTime = System.nanoTime() - solutionStartTime;
       this.solutionTimeString = String.format("%.2f", elapsedSolutionTime / 1000000000.0);
       this.statusString = STATUS_SOLUTION_TIMEOUT;
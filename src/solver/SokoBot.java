@@ -1,86 +1,244 @@
 package solver;
 
 import java.io.*;
+import java.nio.charset.StandardCharsets;
 import java.util.ArrayList;
 import java.util.List;
+import java.util.Map;
+import java.util.concurrent.CompletableFuture;
+import java.util.concurrent.ConcurrentHashMap;
+import java.util.concurrent.ExecutionException;
 
 public class SokoBot {
-    
+    // Keep below the GUI's SOLUTION_TIME_LIMIT so the solver stops cleanly
+    private static final String SOLVER_TIME_LIMIT_SECONDS = "14";
+
+    // One `rust_solver serve` process shared by every bot, started on first
+    // use so later solves skip the process start-up
+    private static Process server;
+    private static BufferedWriter requests;
+    private static int nextRequestId = 0;
+    // Final response line of each unfinished request, completed by the
+    // server's reader thread; null if the server exits first
+    private static final Map<Integer, CompletableFuture<String>> pending = new ConcurrentHashMap<>();
+
+    private volatile int runningRequestId = -1;
+    private volatile boolean cancelled = false;
+
+
     public String solveSokobanPuzzle(int width, int height, char[][] mapData, char[][] itemsData) {
         try {
-            File tempInput = File.createTempFile("sokoban_puzzle", ".txt");
-            tempInput.deleteOnExit();
-            
-            writePuzzleToFile(tempInput, width, height, mapData, itemsData);
-            String solution = callRustSolver(tempInput.getAbsolutePath());
-            
+            String puzzle = formatPuzzle(width, height, mapData, itemsData);
+            String solution = callRustSolver(puzzle);
+
             return solution != null ? solution : "";
-            
+
         } catch (Exception e) {
             e.printStackTrace();
             return "";
         }
     }
-    
-    private void writePuzzleToFile(File file, int width, int height, char[][] mapData, char[][] itemsData) throws IOException {
-        try (BufferedWriter writer = new BufferedWriter(new FileWriter(file))) {
-            for (int y = 0; y < height; y++) {
-                StringBuilder line = new StringBuilder();
-                for (int x = 0; x < width; x++) {
-                    char tile = mapData[y][x];
-                    char item = itemsData[y][x];
-                    
-                    if (item == '@') {
-                        line.append(tile == '.' ? '+' : '@');
-                    } else if (item == '$') {
-                        line.append(tile == '.' ? '*' : '$');
-                    } else if (tile == '.') {
-                        line.append('.');
-                    } else if (tile == '#') {
-                        line.append('#');
-                    } else {
-                        line.append(' ');
-                    }
+
+    // Stops the running search; solveSokobanPuzzle then returns ""
+    public void cancel() {
+        cancelled = true;
+        int id = runningRequestId;
+        if (id < 0) {
+            return;
+        }
+        try {
+            sendRequest("{\"type\":\"cancel\",\"target\":" + id + "}");
+        } catch (IOException e) {
+            e.printStackTrace();
+        }
+    }
+
+    public boolean wasCancelled() {
+        return cancelled;
+    }
+
+    private String formatPuzzle(int width, int height, char[][] mapData, char[][] itemsData) {
+        StringBuilder puzzle = new StringBuilder();
+        for (int y = 0; y < height; y++) {
+            for (int x = 0; x < width; x++) {
+                char tile = mapData[y][x];
+                char item = itemsData[y][x];
+
+                if (item == '@') {
+                    puzzle.append(tile == '.' ? '+' : '@');
+                } else if (item == '$') {
+                    puzzle.append(tile == '.' ? '*' : '$');
+                } else if (tile == '.') {
+                    puzzle.append('.');
+                } else if (tile == '#') {
+                    puzzle.append('#');
+                } else {
+                    puzzle.append(' ');
                 }
-                writer.write(line.toString());
-                writer.newLine();
             }
+            puzzle.append('\n');
         }
+        return puzzle.toString();
     }
-    
-    private String callRustSolver(String inputFilePath) throws IOException, InterruptedException {
+
+    private static synchronized void startServer() throws IOException {
+        if (server != null && server.isAlive()) {
+            return;
+        }
         List<String> command = new ArrayList<>();
         command.add("./rust_solver");
-        command.add(inputFilePath);
-        
+        command.add("serve");
+
         ProcessBuilder processBuilder = new ProcessBuilder(command);
-        processBuilder.redirectErrorStream(true);
-        
+        processBuilder.redirectError(ProcessBuilder.Redirect.INHERIT);
+
         Process process = processBuilder.start();
-        
-        StringBuilder solution = new StringBuilder();
-        try (BufferedReader reader = new BufferedReader(new InputStreamReader(process.getInputStream()))) {
+        server = process;
+        requests = new BufferedWriter(new OutputStreamWriter(process.getOutputStream(), StandardCharsets.UTF_8));
+        BufferedReader responses =
+                new BufferedReader(new InputStreamReader(process.getInputStream(), StandardCharsets.UTF_8));
+        Thread reader = new Thread(() -> readResponses(responses), "rust_solver responses");
+        reader.setDaemon(true);
+        reader.start();
+        Runtime.getRuntime().addShutdownHook(new Thread(process::destroy));
+    }
+
+    // Hands each request its result or error line; progress lines and lines
+    // for requests nobody waits for any more are dropped
+    private static void readResponses(BufferedReader responses) {
+        try {
             String line;
-            while ((line = reader.readLine()) != null) {
-                solution.append(line);
+            while ((line = responses.readLine()) != null) {
+                String event = stringField(line, "event");
+                if (!"result".equals(event) && !"error".equals(event)) {
+                    continue;
+                }
+                Integer id = idField(line);
+                CompletableFuture<String> response = id == null ? null : pending.remove(id);
+                if (response != null) {
+                    response.complete(line);
+                }
             }
+        } catch (IOException e) {
+            e.printStackTrace();
         }
-        
-        boolean completed = process.waitFor(30, java.util.concurrent.TimeUnit.SECONDS);
-        
-        if (!completed) {
-            process.destroyForcibly();
-            System.err.println("Rust solver timed out");
-            return "";
+        for (Integer id : pending.keySet()) {
+            CompletableFuture<String> response = pending.remove(id);
+            if (response != null) {
+                response.complete(null);
+            }
         }
-        
-        int exitCode = process.exitValue();
-        
-        if (exitCode == 0) {
-            return solution.toString().trim();
-        } else {
-            System.err.println("Rust solver exited with code: " + exitCode);
+    }
+
+    private static synchronized void sendRequest(String request) throws IOException {
+        requests.write(request);
+        requests.newLine();
+        requests.flush();
+    }
+
+    private static synchronized int takeRequestId() {
+        return nextRequestId++;
+    }
+
+    private String callRustSolver(String puzzle) throws IOException, InterruptedException {
+        startServer();
+        int id = takeRequestId();
+        CompletableFuture<String> response = new CompletableFuture<>();
+        pending.put(id, response);
+        runningRequestId = id;
+        try {
+            sendRequest("{\"id\":" + id + ",\"type\":\"solve\",\"puzzle\":" + jsonString(puzzle)
+                    + ",\"progress_ms\":0,\"options\":{\"time_limit\":" + SOLVER_TIME_LIMIT_SECONDS + "}}");
+            // A key press may have come in before the request was sent
+            if (cancelled) {
+                cancel();
+            }
+
+            String line;
+            try {
+                line = response.get();
+            } catch (ExecutionException e) {
+                line = null;
+            }
+            if (line == null) {
+                System.err.println("Rust solver exited unexpectedly");
+                return "";
+            }
+            if ("error".equals(stringField(line, "event"))) {
+                System.err.println("Rust solver rejected the request: " + stringField(line, "error"));
+                return "";
+            }
+            String termination = stringField(line, "termination");
+            if ("solved".equals(termination)) {
+                return stringField(line, "solution");
+            }
+            System.err.println("Rust solver failed: " + describeTermination(termination));
             return "";
+        } finally {
+            runningRequestId = -1;
+            pending.remove(id);
+        }
+    }
+
+    private static String jsonString(String text) {
+        StringBuilder json = new StringBuilder("\"");
+        for (char c : text.toCharArray()) {
+            switch (c) {
+                case '"': json.append("\\\""); break;
+                case '\\': json.append("\\\\"); break;
+                case '\n': json.append("\\n"); break;
+                case '\r': json.append("\\r"); break;
+                case '\t': json.append("\\t"); break;
+                default: json.append(c);
+            }
+        }
+        return json.append('"').toString();
+    }
+
+    // Value of a string field in a response line; the fields read here never
+    // contain escaped quotes
+    private static String stringField(String line, String key) {
+        String marker = "\"" + key + "\":\"";
+        int start = line.indexOf(marker);
+        if (start < 0) {
+            return null;
+        }
+        start += marker.length();
+        int end = line.indexOf('"', start);
+        return end < 0 ? null : line.substring(start, end);
+    }
+
+    // Request id of a response line; ids are always sent as numbers, and the
+    // preceding '{' or ',' skips any "id" quoted inside a string value
+    private static Integer idField(String line) {
+        for (String marker : new String[] {"{\"id\":", ",\"id\":"}) {
+            int start = line.indexOf(marker);
+            if (start < 0) {
+                continue;
+            }
+            start += marker.length();
+            int end = start;
+            while (end < line.length() && Character.isDigit(line.charAt(end))) {
+                end++;
+            }
+            return end == start ? null : Integer.valueOf(line.substring(start, end));
+        }
+        return null;
+    }
+
+    private String describeTermination(String termination) {
+        if (termination == null) {
+            return "no result";
+        }
+        switch (termination) {
+            case "parse": return "malformed puzzle";
+            case "invalid-puzzle": return "invalid puzzle";
+            case "unsolvable": return "no solution exists";
+            case "timeout": return "time limit reached";
+            case "out-of-memory": return "out of memory";
+            case "cancelled": return "cancelled";
+            case "node-limit": return "node limit reached";
+            default: return termination;
         }
     }
 }